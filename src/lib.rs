@@ -163,19 +163,96 @@
 //! enum ...
 //! ```
 //!
-//! Any attributes and comments can be put to the common fields as normal `enum` fields.
+//! Multiple `#[common_fields]` attributes can be stacked on one enum to group related common fields. Common
+//! fields of all the attributes are accumulated in order, and options put to any of them are applied to all
+//! common fields. Putting the same attribute twice by mistake is reported as an error.
+//!
+//! Any attributes and comments can be put to the common fields as normal `enum` fields. Attributes put to
+//! the enum such as `#[derive(...)]` or `#[non_exhaustive]` are also kept as-is. Note that `#[derive(...)]`
+//! must be put below `#[common_fields]`. Attributes are expanded from top to bottom, so a derive above
+//! `#[common_fields]` sees the enum before common fields are injected and causes a compile error. The
+//! attribute macro cannot see such derives, but it reports the mistake when it finds their helper
+//! attributes such as `#[default]` or `#[serde(...)]` without the derive below. Since all generated code
+//! is put in the same crate as the enum, `#[non_exhaustive]` does not affect it.
+//! `#[repr(...)]` is also kept, but note that variants have fields after injecting common fields so the
+//! enum is no longer C-like. For C-like discriminant, please use the kind enum with `kind(repr(...))`
+//! option. Generated code refers to items in the standard library with fully qualified paths such as
+//! `::core::option::Option`, so it works even if names like `Option` or `bool` are shadowed where the enum
+//! is defined. Generated code only depends on `core` so the macros can be used in `#![no_std]` crates,
+//! except for `shared` option and `#[cow]` which require `std`. Since primitive types are referred to as
+//! `::core::primitive::bool`, generated code requires Rust 1.43 or later.
 //!
 //! Accessor methods corresponding to common fields are defined. It is a useful helper to access common
-//! fields without using pattern match.
+//! fields without using pattern match. For each common field `x`, `x()` returns an immutable reference.
+//! Mutable accessors are opt-in with `accessors` option. `x_mut()` returns a mutable reference and `set_x()`
+//! overwrites the field with a new value. Like `std::mem::replace`, `replace_x()` overwrites the field and
+//! returns the previous value. It is not defined with `trait` and `impl_trait` options so that other
+//! implementations of the trait don't need it. Doc comments of a common field are also put to its getter
+//! `x()`, and `#[cfg(...)]` attributes of the field are put to all its accessors and the other generated
+//! code using the field so that a feature-gated common field works as expected. Similarly `#[cfg(...)]`
+//! attributes of a variant are put to match arms and items generated for the variant such as `is_a()`.
+//! `#[deprecated]` attributes of the field are also put to its accessors so that calling them is warned.
+//! Accessors are also documented with the variants which have the field such as
+//! "Common field available on all variants: GitHub, GitLab."
+//!
+//! The name of accessors can be changed with `#[accessor(name = "...")]` attribute at a common field. It
+//! is useful when the field name is not a good method name or conflicts with other methods. For example,
+//! with `#[accessor(name = "repo_user")] user: String`, the field is still `user` but its accessors are
+//! `repo_user()`, `repo_user_mut()` and `set_repo_user()`. A common field can be named with a raw
+//! identifier such as `r#type: String`. Then its accessors are `r#type()`, `type_mut()` and `set_type()`.
+//!
+//! `AsRef` can be implemented for the enum by delegating to a common field with `#[as_ref]` attribute.
+//! `#[as_ref]` implements `AsRef<T>` where `T` is the type of the field, and `#[as_ref(str, [u8])]`
+//! implements `AsRef<str>` and `AsRef<[u8]>` via the `AsRef` implementations of the field type. The same
+//! target type cannot be put to more than one common field.
+//!
+//! For a common field marked with `#[pin]`, a pin projection accessor such as
+//! `fn x_pin_mut(self: Pin<&mut Self>) -> Pin<&mut T>` is defined in addition. It is useful for `!Unpin`
+//! common fields. The field is structurally pinned so the enum is `Unpin` only when all such fields are
+//! `Unpin`. Implementing `Unpin` or `Drop` for the enum causes a compilation error since they could move the
+//! field out. Since the accessor uses `unsafe` internally, it cannot be used with `#![forbid(unsafe_code)]`.
+//!
+//! The getter of a common field marked with `#[deref]` returns a reference to the `Deref` target of the
+//! field type instead of the field type itself. For example, with `#[deref] user: String`, `user()` returns
+//! `&str` instead of `&String`. `Vec<T>` is accessed as `&[T]` and `Box<T>` as `&T` in the same way. Other
+//! accessors such as `user_mut()` are not affected. The field type must implement `Deref`, otherwise the
+//! generated getter causes a compile error.
+//!
+//! The getter of a common field marked with `#[cow]` returns `Cow` of the `Deref` target such as
+//! `fn name(&self) -> Cow<'_, str>` for `#[cow] name: String`. It is useful when the field is combined with
+//! `#[variant_optional]` and a default value such as `#[cow] #[variant_optional] name: String = "".into()`.
+//! Then the getter borrows the field for variants which have it and returns the owned default value for
+//! variants which skip it, instead of returning `Option`. Without a default value, the getter of an optional
+//! field returns `Option<Cow<'_, str>>`. The owned type of the deref target must be the field type itself,
+//! as `String` for `str` and `Vec<T>` for `[T]`. `#[cow]` cannot be used with `shared` option and the getter
+//! is not `const fn` even if `const` option is set. Since `Cow` is not in `core`, `#[cow]` requires `std`.
+//!
+//! When a common field is marked with `#[ord_key]`, `PartialOrd` and `Ord` are implemented for the enum.
+//! They compare values by the getter of the field such as `#[ord_key] stars: u32`, so a `Vec` of the enum
+//! can be sorted by the stars with `sort()`. Only one common field can be marked with `#[ord_key]`. Since
+//! `Ord` requires `Eq`, please implement `PartialEq` and `Eq` consistent with the ordering by yourself.
+//!
+//! When a common field is marked with `#[display]`, `Display` is implemented for the enum. It writes the
+//! value of the field such as `#[display] name: String`, so `format!("{}", repo)` yields the name. Only one
+//! common field can be marked with `#[display]`. When it is put to a `#[variant_optional]` field, variants
+//! which don't have the field write nothing.
+//!
+//! In addition, predicate methods to check the variant are defined. For each variant `A`, `is_a()` returns
+//! `true` when the value is the variant. The variant name is converted into snake case. For example,
+//! `is_git_hub()` for `GitHub` variant. `variant_name()` returns the name of the variant as `&'static str`
+//! such as `"GitHub"`. It is handy for logging without defining the kind enum by `kind` option. And
+//! associated function `common_field_names()` returns names of all common fields as
+//! `&'static [&'static str]` in the declaration order. It is useful to enumerate the shared keys for
+//! logging or serialization.
 //!
 //! For example,
 //!
 //! ```rust,ignore
-//! #[common_fields { i: i32 }]
+//! #[common_fields(accessors = "get,set,mut", { i: i32 })]
 //! enum E { A, B{ b: bool } }
 //! ```
 //!
-//! Generates an accessor method for `i` as follows:
+//! Generates accessor methods for `i` as follows:
 //!
 //! ```rust,ignore
 //! impl E {
@@ -185,17 +262,275 @@
 //!             E::B{ref i, ..} => i,
 //!         }
 //!     }
+//!
+//!     fn i_mut(&mut self) -> &mut i32 {
+//!         match self {
+//!             E::A{ref mut i, ..} => i,
+//!             E::B{ref mut i, ..} => i,
+//!         }
+//!     }
+//!
+//!     fn set_i(&mut self, value: i32) {
+//!         match self {
+//!             E::A{i: ref mut field, ..} => *field = value,
+//!             E::B{i: ref mut field, ..} => *field = value,
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Generic enums are also supported. Common fields can use type parameters, lifetime parameters and const
+//! parameters of the enum and the accessors are implemented with the same generic parameters and `where`
+//! clause. Note that an enum defined in a generic function cannot use the generic parameters of the function
+//! as with normal Rust items, so please declare its own generic parameters.
+//!
+//! Common fields can refer to the enum itself with `Self` such as `next: Option<Box<Self>>`. `Self` is
+//! replaced with the concrete enum type so that it means the enum also in generated structs.
+//!
+//! Methods which only depend on common fields can be defined once with `#[common_methods]` attribute macro.
+//! The methods are put in an `impl` block of the enum.
+//!
+//! ```rust,ignore
+//! #[common_fields { user: String, name: String }]
+//! #[common_methods {
+//!     pub fn full_name(&self) -> String {
+//!         format!("{}/{}", self.user(), self.name())
+//!     }
+//! }]
+//! enum RemoteRepo { ... }
+//! ```
+//!
+//! `common_match!` macro binds multiple common fields of an enum value at once and evaluates the body with
+//! them. The fields are matched by patterns of all variants and bound as references such as `&T`, or as
+//! `Option<&T>` for `#[variant_optional]` fields. Since they are bound by hidden methods generated by
+//! `#[common_fields]`, they don't depend on names and kinds of accessors, while the macro cannot be used
+//! with `no_accessors` option.
+//!
+//! ```rust,ignore
+//! use diff_enum::common_match;
+//!
+//! let full_name = common_match!(repo, { user, name } => format!("{}/{}", user, name));
+//! ```
+//!
+//! A common field which only some variants have can be marked with `#[variant_optional]`. Variants which
+//! don't have the field skip it with `#[common_fields(skip(...))]` attribute. Accessors of the field return
+//! `Option` such as `fn stars(&self) -> Option<&u32>` and `fn stars_mut(&mut self) -> Option<&mut u32>`,
+//! which return `None` for the skipping variants. `set_stars()` is not defined for the field so please use
+//! `stars_mut()` instead. `#[common_fields(skip)]` without field names skips all common fields. It is useful
+//! for sentinel variants such as `Invalid` which should not carry any common field. In this case all common
+//! fields must be marked with `#[variant_optional]`.
+//!
+//! ```rust,ignore
+//! #[common_fields {
+//!     name: String,
+//!     #[variant_optional]
+//!     stars: u32,
+//! }]
+//! enum RemoteRepo {
+//!     GitHub { language: String },
+//!     #[common_fields(skip(stars))]
+//!     Local,
+//! }
+//! ```
+//!
+//! When a field is shared by a subset of variants, the field can be put to groups with `#[group(...)]`
+//! instead of skipping it at each variant. Variants join groups with `#[groups(...)]` and only variants
+//! joining one of the groups of the field have the field. The field is treated as `#[variant_optional]` so
+//! its accessors return `Option`. Groups can overlap with each other.
+//!
+//! ```rust,ignore
+//! #[common_fields {
+//!     name: String,
+//!     #[group(remote)]
+//!     api_url: String,
+//!     #[group(remote, mirror)]
+//!     last_sync: u64,
+//! }]
+//! enum Repo {
+//!     #[groups(remote)]
+//!     GitHub { stars: u32 },
+//!     #[groups(mirror)]
+//!     Mirror,
+//!     Local,
 //! }
 //! ```
 //!
+//! ## Options
+//!
+//! Options can be put before common fields in the attribute argument. Options and common fields are
+//! separated by commas, and common fields can be grouped with braces. Without braces, common fields can
+//! also be listed in the parentheses such as `#[common_fields(prepend, user: String, name: String)]`.
+//!
+//! ```ignore
+//! #[common_fields(prepend, {
+//!     user: String,
+//!     name: String,
+//! })]
+//! enum ...
+//! ```
+//!
+//! - `prepend`: Common fields are inserted at the head of each variant's fields. By default they are
+//!   appended to the tail. In both cases common fields keep the order in the attribute argument, so the
+//!   order of fields in each variant, which is used by derived traits such as `Debug` and `PartialOrd`, is
+//!   deterministic.
+//! - `vis = "..."`: Visibility of generated accessor methods such as `vis = "pub(crate)"`. By default
+//!   it is the same as the visibility of the enum.
+//! - `prefix = "..."`: The prefix is prepended to names of all common fields such as `prefix = "common_"`.
+//!   Then common field `id` is injected into variants as `common_id` and its getter is `common_id()`. It
+//!   is useful to avoid name clashes with variant-specific fields. `skip(...)` at variants still takes the
+//!   names without the prefix.
+//! - `case = "..."`: How variant names are converted in names of generated methods such as predicates,
+//!   views, builders and constructors. `"snake"` (default) converts `GitHub` into `git_hub` so the
+//!   predicate is `is_git_hub()`. `"lower"` only lowercases the name so the predicate is `is_github()`.
+//! - `copy`: Getters return values of common fields by copy like `fn x(&self) -> i32` instead of
+//!   references. All common fields must implement `Copy`.
+//! - `inline = "..."`: Controls the inline attribute of accessor methods. `"hint"` puts `#[inline]`,
+//!   `"always"` puts `#[inline(always)]`, `"never"` puts `#[inline(never)]` and `"none"` puts nothing.
+//!   By default it is `"hint"`.
+//! - `must_use`: `#[must_use]` is put to getters, predicates, `variant_name()` and `common_refs()` so that
+//!   ignoring their results is warned by the compiler. By default it is not put.
+//! - `accessors = "..."`: Accessors defined for each common field. The value is a comma-separated list of
+//!   `get` for getter `x()`, `set` for setters `set_x()` and `replace_x()`, and `mut` for mutable getter
+//!   `x_mut()` such as `accessors = "get,set,mut"`. By default only getters are defined. Options which
+//!   call getters such as `partial_eq` require `get`.
+//! - `warn_unused`: `#[allow(dead_code)]` is not put to accessors and predicates so that unused ones are
+//!   warned by the compiler. It is useful to find unnecessary API surface. By default they are not warned.
+//!   Generated impl blocks are marked with `#[automatically_derived]` as `#[derive]` does, but the impl
+//!   block of accessors and predicates is not marked with this option since the attribute also
+//!   suppresses the warnings.
+//! - `no_accessors`: Common fields are only injected into variants. No accessor method nor predicate
+//!   method is defined. This option cannot be used with options which depend on accessors such as
+//!   `partial_eq`, `hash`, `trait`, `impl_trait`, `const` and `common_refs`.
+//! - `const`: Getters and predicates are defined as `const fn` so that they can be used in const contexts
+//!   such as `const X: i32 = FOO.x();`. Other accessors are not affected since they mutate or move fields.
+//!   Since `match` expression in `const fn` is stabilized at Rust 1.46, this option requires Rust 1.46 or
+//!   later. Combined with `copy` option, getters can read values of `Copy` common fields in const contexts.
+//!   This option cannot be used with `trait` and `impl_trait` options because trait methods cannot be
+//!   `const fn`.
+//! - `where ...`: Additional bounds for generic parameters such as `where T: Clone`. They are merged into
+//!   the `where` clause of the enum and all generated implementations. It is useful when a common field
+//!   requires some bound to be a valid type. Since predicates are also separated by commas, the `where`
+//!   clause must be put at the end of the argument or just before common fields grouped with braces.
+//! - `into`: For each common field `x`, `into_x()` method is defined. It consumes the enum value and
+//!   returns the owned field value.
+//! - `common_refs`: `common_refs()` method is defined. It returns references to all common fields as a
+//!   tuple in the declaration order such as `let (user, name) = repo.common_refs();`. Optional common
+//!   fields are returned as `Option`. Common fields with `#[cfg(...)]` cannot be used with this option.
+//! - `common_struct`: A struct which only contains common fields is defined. Its name is the enum name
+//!   with `Common` suffix such as `RemoteRepoCommon`. `into_common()` method moves the common fields of
+//!   the enum into the struct. `From` is also implemented so that `let c: RemoteRepoCommon = repo.into()`
+//!   works. Visibility of a common field such as `pub(crate) user: String` is used for the field of the
+//!   struct. It is not put to the fields injected into variants since they are always as visible as the
+//!   enum. Without moving the enum, `common()` method returns a struct of references to the common fields
+//!   such as `RemoteRepoCommonRef<'_>` whose fields are `&'a T`. For each variant, a constructor which
+//!   takes the common struct and variant-specific fields is defined such as
+//!   `RemoteRepo::git_hub_with_common(common, language, pull_requests)`. It is not defined for tuple
+//!   variants and variants which have `#[variant_optional]` fields since the fields may be `None` in the
+//!   struct.
+//! - `kind`: A field-less enum which has the same variants is defined. Its name is the enum name with
+//!   `Kind` suffix such as `RemoteRepoKind`. `kind()` method returns the kind of the variant. The kind
+//!   enum derives `Debug`, `Clone`, `Copy`, `PartialEq` and `Eq`. Its visibility and additional derives can
+//!   be specified like `kind(vis = "pub", derive(Hash, Serialize))`. By default the visibility is the same
+//!   as the enum. `kind(repr(u8))` puts `#[repr(u8)]` to the kind enum so that it can be used as C-like
+//!   discriminant such as `repo.kind() as u8`.
+//! - `partial_eq`: `PartialEq` is implemented for the enum. Two values are equal when all their common
+//!   fields are equal. Variants and other fields are not compared. Don't use this with
+//!   `#[derive(PartialEq)]`.
+//! - `hash`: `Hash` is implemented for the enum. Only common fields are hashed. Please enable this with
+//!   `partial_eq` option together so that `Hash` is consistent with `PartialEq`. To put the values in
+//!   `HashSet` or `HashMap`, implement `Eq` with `impl Eq for E {}` additionally.
+//! - `allow_override`: When a variant already declares a field with the same name as a common field, the
+//!   common field is not added to the variant and the accessor uses the variant's field. The variant's
+//!   field must have the same type as the common field. Without this option it causes a compilation error.
+//! - `builder`: A builder struct is defined for each variant. Its name is the enum name followed by the
+//!   variant name with `Builder` suffix such as `RemoteRepoGitHubBuilder`, and it is created by
+//!   `git_hub_builder()`. Each field of the variant including common fields is set by the method with the
+//!   same name, and `build()` returns the enum value. `build()` returns an error message when some field
+//!   is not set. Builders are not defined for tuple variants. A common field can have a default value such
+//!   as `stars: u32 = 0`, which is used by `build()` when the field is not set. The expression is evaluated
+//!   in the generated code so names in it must be visible where the enum is defined, otherwise rustc
+//!   reports an error.
+//! - `transition`: For each variant, a method to change the variant of the value while keeping its common
+//!   fields is defined such as `repo.with_git_lab(merge_requests)`. It consumes the enum value and takes
+//!   only variant-specific fields of the new variant as arguments. Optional common fields are dropped when
+//!   the new variant skips them. The method is not defined for tuple variants, and for variants which have
+//!   an optional common field skipped by some other variant since its value cannot be carried over.
+//! - `constructor`: For each variant, a constructor which takes all fields of the variant as arguments is
+//!   defined such as `RemoteRepo::new_git_hub(language, pull_requests, user, name)`. The arguments are in
+//!   the order of the fields after injecting common fields. It is simpler than a builder for small variants.
+//! - `map`: For each common field `x`, `map_x(self, f: impl FnOnce(T) -> T) -> Self` is defined. It
+//!   consumes the enum value and returns it with the field transformed by `f` such as
+//!   `let repo = repo.map_user(|u| u.to_uppercase());`. The variant and other fields are kept as-is. For
+//!   variants which skip an optional common field, the value is returned without calling `f`.
+//! - `serde_flatten`: `Serialize` and `Deserialize` of [serde](https://serde.rs/) are implemented for the
+//!   enum. Common fields are put at the top level and variant-specific fields are nested in an object keyed
+//!   by the variant name such as `{"user": "rhysd", "name": "foo", "GitHub": {"issues": 3}}`. Optional
+//!   common fields are omitted when the variant skips them. Variant-specific fields of tuple variants are
+//!   nested as a value or an array. The crate using this option must depend on `serde` crate with `derive`
+//!   feature since the generated code uses `#[derive(Serialize, Deserialize)]` for helper types. Type
+//!   parameters of the enum are required to implement `Serialize` and `Deserialize`.
+//! - `shared`: Values of common fields are shared via `Arc` so that cloning the enum value only increments
+//!   reference counts instead of cloning the values. The type of each common field `x: T` is changed to
+//!   `x: Arc<T>` in variants, so constructing the enum requires `Arc::new(value)`. A default value such
+//!   as `x: i32 = 3` is still given as `T` and put into `Arc` automatically. The getter `x()`
+//!   returns `&T` through the `Arc`, or `T` with `copy` option, while `x_mut()`, `set_x()` and `into_x()`
+//!   handle `Arc<T>`. For example, `Arc::make_mut(repo.user_mut())` modifies the value with
+//!   clone-on-write. Each common field has its own `Arc` so cloning the enum value increments as many
+//!   reference counts as common fields. It is by design: common fields stay normal fields of each variant,
+//!   so that patterns such as `E::A { x, .. }` and the other options keep working. One `Arc` holding all
+//!   common fields would need a hidden field in all variants instead. With `serde_flatten` option, `rc`
+//!   feature of `serde` crate is necessary to serialize `Arc`. `#[deref]` and `#[cow]` cannot be used with
+//!   this option since the getters already return references to the inner values.
+//! - `default = "..."`: `Default` is implemented for the enum. `default()` returns the variant with the
+//!   given name such as `default = "GitLab"`, whose fields including common fields are set to their
+//!   default values. All the fields of the variant must implement `Default` except for common fields which
+//!   have default values such as `stars: u32 = 0`.
+//! - `view`: A struct to borrow variant-specific fields is defined for each variant. Its name is the enum
+//!   name followed by the variant name with `Ref` suffix such as `RemoteRepoGitHubRef<'a>`, and its fields
+//!   are references to the fields of the variant except for common fields. `as_git_hub()` returns
+//!   `Some` of the view when the value is the variant, otherwise `None`. Variants which have no
+//!   variant-specific field are skipped since `is_git_hub()` is enough for them.
+//! - `tuple`: Tuple variants such as `A(bool)` are allowed. Common fields are injected into them as
+//!   trailing positional fields such as `A(bool, i32)`, or leading ones with `prepend` option, and
+//!   accessors access the fields by their positions. Unit variants also become tuple variants with this
+//!   option. Since the arity of tuple variants is changed, constructing them requires values of common
+//!   fields. Common fields with `#[cfg(...)]` cannot be injected into tuple variants.
+//! - `trait = "..."`: Accessor methods are defined in a trait with the given name such as
+//!   `trait = "HasRepoInfo"` instead of inherent methods, and the trait is implemented for the enum.
+//!   Functions can be generic over enums which share the common fields with the trait. Predicates such as
+//!   `is_a()` are still inherent methods. The trait can also be implemented for types in other crates.
+//!   Even if the enum is `#[non_exhaustive]`, only matches written in other crates need wildcard arms.
+//! - `impl_trait = "..."`: Accessor methods implement an existing trait at the given path, which was
+//!   generated by `trait` option for another enum. The common fields and the options which affect
+//!   accessors such as `copy` or `into` must be the same as the enum defining the trait.
+//!
 //! ## Errors
 //!
 //! The attribute macro causes compilation errors in the following cases.
 //!
 //! - When no common field is put
+//! - When the same common field is put more than once
+//! - When an unknown option is put
 //! - When fields in attribute argument is not form of `field: type`
 //! - When `#[common_fields {...}]` is set to other than `enum` definitions
-//! - When tuple style enum variant is used in `enum` definition
+//! - When tuple style enum variant is used in `enum` definition without `tuple` option
+//! - When a common field is already declared in some enum variant
+//! - When both `trait` and `impl_trait` options are put
+//! - When `const` option is put with `trait` or `impl_trait` option
+//! - When a method generated for a common field has the same name as other generated method such as a
+//!   common field `kind` with `kind` option
+//! - When `#[as_ref]` for the same type is put to more than one common field
+//! - When explicit discriminants such as `A = 1` are put to variants of the enum without `#[repr(...)]`
+//! - When the variant given to `default` option does not exist
+//! - When a variant skips a common field which is unknown or not marked with `#[variant_optional]`
+//! - When a group put to a variant with `#[groups(...)]` is not given to any common field
+//! - When a field overriding a common field with `allow_override` option has a different type
+//! - When `#[ord_key]` is put to more than one common field
+//! - When `#[display]` is put to more than one common field
+//! - When `#[common_fields]` with the same arguments is stacked on the enum more than once
+//! - When stacked `#[common_fields]` has no argument or its argument is not enclosed in `(...)` or `{...}`
+//! - When a helper attribute of derive such as `#[default]` is put without the derive below
+//!   `#[common_fields]`
 
 extern crate proc_macro;
 extern crate proc_macro2;
@@ -203,105 +538,5040 @@ extern crate quote;
 extern crate syn;
 
 use proc_macro::TokenStream;
-use proc_macro2::TokenStream as TokenStream2;
-use proc_macro_attribute;
-use quote::quote;
-use syn::{Data, DeriveInput, Fields, FieldsNamed, Ident};
+use proc_macro2::{Group, Span, TokenStream as TokenStream2, TokenTree};
+use quote::{quote, quote_spanned};
+use std::collections::{HashMap, HashSet};
+use syn::parse::{Parse, ParseStream, Parser};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{braced, token, Token};
+use syn::{
+    Attribute, Data, DeriveInput, Error, Expr, Field, Fields, FieldsNamed, FieldsUnnamed,
+    GenericParam, Generics, Ident, ImplItemMethod, Item, Lit, LitStr, Meta, MetaNameValue,
+    NestedMeta, Path, Result, Type, Variant, Visibility, WhereClause,
+};
 
 #[proc_macro_attribute]
 pub fn common_fields(attr: TokenStream, item: TokenStream) -> TokenStream {
-    let shared: FieldsNamed = parse_shared_fields(attr);
-    if shared.named.is_empty() {
-        panic!("No shared field is set to #[diff_enum::common_fields]");
+    match expand_common_fields(attr.into(), item.into()) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Defines methods shared among all variants of the enum. The methods written in the attribute argument
+/// are put in an `impl` block of the enum. Methods can use accessors generated by `#[common_fields]`.
+///
+/// ```ignore
+/// #[common_fields { user: String, name: String }]
+/// #[common_methods {
+///     pub fn full_name(&self) -> String {
+///         format!("{}/{}", self.user(), self.name())
+///     }
+/// }]
+/// enum RemoteRepo { ... }
+/// ```
+#[proc_macro_attribute]
+pub fn common_methods(attr: TokenStream, item: TokenStream) -> TokenStream {
+    match expand_common_methods(attr.into(), item.into()) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand_common_methods(attr: TokenStream2, item: TokenStream2) -> Result<TokenStream2> {
+    let methods = parse_common_methods(attr)?;
+    if methods.is_empty() {
+        return Err(Error::new(
+            Span::call_site(),
+            "No method is set to #[diff_enum::common_methods]",
+        ));
     }
 
-    let input: DeriveInput = match syn::parse(item) {
+    let input = parse_enum("common_methods", item.clone())?;
+
+    let enum_name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        #item
+        impl #impl_generics #enum_name #ty_generics #where_clause {
+            #( #methods )*
+        }
+    })
+}
+
+fn parse_common_methods(attr: TokenStream2) -> Result<Vec<ImplItemMethod>> {
+    let parser = |input: ParseStream| {
+        let mut methods = vec![];
+        while !input.is_empty() {
+            methods.push(input.parse()?);
+        }
+        Ok(methods)
+    };
+    match parser.parse2(attr) {
+        Ok(methods) => Ok(methods),
+        Err(err) => Err(Error::new(
+            err.span(),
+            format!(
+                "Cannot parse methods in attributes at #[diff_enum::common_methods]: {}",
+                err
+            ),
+        )),
+    }
+}
+
+/// Binds common fields of the enum value at once. The common fields listed in braces are bound to
+/// the variables with the same names as references by matching all variants, and then the body is
+/// evaluated.
+///
+/// ```ignore
+/// let full_name = common_match!(repo, { user, name } => format!("{}/{}", user, name));
+/// ```
+#[proc_macro]
+pub fn common_match(input: TokenStream) -> TokenStream {
+    match expand_common_match(input.into()) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Input of `common_match!`: `value, { field, ... } => body`
+struct CommonMatch {
+    value: Expr,
+    fields: Punctuated<Ident, Token![,]>,
+    body: Expr,
+}
+
+impl Parse for CommonMatch {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let value = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let content;
+        braced!(content in input);
+        let fields = content.parse_terminated(Ident::parse)?;
+        input.parse::<Token![=>]>()?;
+        let body = input.parse()?;
+        if input.peek(Token![,]) {
+            input.parse::<Token![,]>()?;
+        }
+        Ok(CommonMatch {
+            value,
+            fields,
+            body,
+        })
+    }
+}
+
+fn expand_common_match(input: TokenStream2) -> Result<TokenStream2> {
+    let CommonMatch {
+        value,
+        fields,
+        body,
+    } = match syn::parse2(input) {
         Ok(parsed) => parsed,
-        Err(err) => panic!(
-            "#[diff_enum::common_fields] only can be set at enum definition: {}",
-            err
-        ),
+        Err(err) => {
+            return Err(Error::new(
+                err.span(),
+                format!(
+                    "Cannot parse arguments of diff_enum::common_match! (expected `value, {{ field, ... }} => body`): {}",
+                    err
+                ),
+            ))
+        }
+    };
+    if fields.is_empty() {
+        return Err(Error::new(
+            Span::call_site(),
+            "No common field is listed in diff_enum::common_match!",
+        ));
+    }
+
+    // The value is matched once so that temporaries live until the body is evaluated as with `match`.
+    // Fields are bound by the hidden methods generated by #[common_fields] which match all variants
+    let values = fields.iter().map(|field| {
+        let method_name = common_match_method_name(field);
+        quote!(__diff_enum_value.#method_name())
+    });
+    let fields = fields.iter();
+    Ok(quote! {
+        match &(#value) {
+            __diff_enum_value => match ( #( #values, )* ) {
+                ( #( #fields, )* ) => #body,
+            },
+        }
+    })
+}
+
+// Name of the hidden method to bind the common field in `common_match!`
+fn common_match_method_name(field: &Ident) -> Ident {
+    Ident::new(
+        &format!("__diff_enum_common_match_{}", unraw(field)),
+        field.span(),
+    )
+}
+
+// Parses the item which the attribute macro is put to. Other than enum is rejected with an error
+// message describing what is found.
+fn parse_enum(macro_name: &str, item: TokenStream2) -> Result<DeriveInput> {
+    let input: DeriveInput = match syn::parse2(item.clone()) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            // Check type alias since it is a common mistake to put the attribute to an alias of enum
+            if let Ok(Item::Type(alias)) = syn::parse2(item) {
+                return Err(Error::new_spanned(
+                    &alias.ident,
+                    format!(
+                        "#[diff_enum::{}] can be set at only enum: expected enum, found type alias '{}'. Please put the attribute to the enum definition",
+                        macro_name, alias.ident
+                    ),
+                ));
+            }
+            return Err(Error::new(
+                err.span(),
+                format!(
+                    "#[diff_enum::{}] only can be set at enum definition: {}",
+                    macro_name, err
+                ),
+            ));
+        }
     };
+    let found = match input.data {
+        Data::Enum(_) => return Ok(input),
+        Data::Struct(_) => "struct",
+        Data::Union(_) => "union",
+    };
+    Err(Error::new_spanned(
+        &input.ident,
+        format!(
+            "#[diff_enum::{}] can be set at only enum: expected enum, found {} '{}'",
+            macro_name, found, input.ident
+        ),
+    ))
+}
+
+// Expands #[common_fields]. This function only depends on proc_macro2 so that it can be tested without
+// running the compiler.
+fn expand_common_fields(attr: TokenStream2, item: TokenStream2) -> Result<TokenStream2> {
+    let mut input = parse_enum("common_fields", item)?;
+    let attr = accumulate_stacked_args(attr, &mut input)?;
+
+    let mut args = parse_args(attr)?;
+    if args.fields.is_empty() {
+        return Err(Error::new(
+            Span::call_site(),
+            "No shared field is set to #[diff_enum::common_fields]",
+        ));
+    }
+    if args.trait_name.is_some() && args.impl_trait.is_some() {
+        return Err(Error::new(
+            Span::call_site(),
+            "'trait' option and 'impl_trait' option cannot be used together",
+        ));
+    }
+    check_derive_order(&args, &input)?;
+    if args.const_fn && (args.trait_name.is_some() || args.impl_trait.is_some()) {
+        return Err(Error::new(
+            Span::call_site(),
+            "'const' option cannot be used with 'trait' option nor 'impl_trait' option since trait methods cannot be const fn",
+        ));
+    }
+    if args.no_accessors {
+        // These options generate code which depends on accessors
+        let conflicts = [
+            ("partial_eq", args.partial_eq),
+            ("hash", args.hash),
+            ("trait", args.trait_name.is_some()),
+            ("impl_trait", args.impl_trait.is_some()),
+            ("const", args.const_fn),
+            ("common_refs", args.common_refs),
+        ];
+        if let Some((name, _)) = conflicts.iter().find(|(_, enabled)| *enabled) {
+            return Err(Error::new(
+                Span::call_site(),
+                format!(
+                    "'{}' option cannot be used with 'no_accessors' option since it requires accessors",
+                    name
+                ),
+            ));
+        }
+    }
+
+    if !args.getter {
+        // These options generate code which calls getters
+        let conflicts = [
+            ("partial_eq", args.partial_eq),
+            ("hash", args.hash),
+            ("#[ord_key]", args.fields.iter().any(|f| f.ord_key)),
+            ("#[display]", args.fields.iter().any(|f| f.display)),
+        ];
+        if let Some((name, _)) = conflicts.iter().find(|(_, enabled)| *enabled) {
+            return Err(Error::new(
+                Span::call_site(),
+                format!(
+                    "'{}' cannot be used without getters. Please add \"get\" to accessors option",
+                    name
+                ),
+            ));
+        }
+    }
+    let mut ord_keys = args.fields.iter().filter(|f| f.ord_key);
+    if let (Some(first), Some(second)) = (ord_keys.next(), ord_keys.next()) {
+        return Err(Error::new_spanned(
+            second.name(),
+            format!(
+                "#[ord_key] is put to more than one common field '{}' and '{}'. Only one common field can be the key of ordering",
+                first.name(),
+                second.name()
+            ),
+        ));
+    }
+    let mut displays = args.fields.iter().filter(|f| f.display);
+    if let (Some(first), Some(second)) = (displays.next(), displays.next()) {
+        return Err(Error::new_spanned(
+            second.name(),
+            format!(
+                "#[display] is put to more than one common field '{}' and '{}'. Only one common field can be displayed",
+                first.name(),
+                second.name()
+            ),
+        ));
+    }
+    if args.no_accessors {
+        if let Some(field) = args.fields.iter().find(|f| f.ord_key) {
+            return Err(Error::new_spanned(
+                field.name(),
+                "#[ord_key] cannot be used with 'no_accessors' option since it requires accessors",
+            ));
+        }
+        if let Some(field) = args.fields.iter().find(|f| f.display) {
+            return Err(Error::new_spanned(
+                field.name(),
+                "#[display] cannot be used with 'no_accessors' option since it requires accessors",
+            ));
+        }
+    }
+
+    args.variants = parse_variant_options(&args, &mut input)?;
+    if let Some(ref prefix) = args.prefix {
+        // Common fields are renamed after resolving `skip(...)` at variants so that the variants can
+        // refer to the fields with the names declared in the attribute
+        let rename = |ident: &Ident| {
+            let name = format!("{}{}", prefix.value(), unraw(ident));
+            syn::parse_str::<Ident>(&name)
+                .map(|renamed| Ident::new(&renamed.to_string(), ident.span()))
+                .map_err(|_| {
+                    Error::new_spanned(
+                        prefix,
+                        format!(
+                            "Prefix \"{}\" given to prefix option makes invalid field name '{}'",
+                            prefix.value(),
+                            name
+                        ),
+                    )
+                })
+        };
+        for field in args.fields.iter_mut() {
+            field.field.ident = Some(rename(field.name())?);
+        }
+        for variant in args.variants.iter_mut() {
+            for ident in variant.skip.iter_mut() {
+                *ident = rename(ident)?;
+            }
+        }
+    }
+    // Bounds in the attribute are required by common fields so they are merged into the enum. Then all
+    // generated implementations also have them.
+    if let Some(ref where_clause) = args.where_clause {
+        input
+            .generics
+            .make_where_clause()
+            .predicates
+            .extend(where_clause.predicates.iter().cloned());
+    }
+    // `Self` in common fields would mean other types in generated structs such as the common struct
+    let self_ty = self_type(&input);
+    for field in args.fields.iter_mut() {
+        field.field.ty = replace_self(&field.field.ty, &self_ty);
+    }
+    if args.shared {
+        if let Some(field) = args.fields.iter().find(|f| f.cow) {
+            // The owned type of `Arc<T>`'s deref target is `T`, not `Arc<T>`
+            return Err(Error::new_spanned(
+                field.name(),
+                format!(
+                    "Common field '{}' with #[cow] cannot be used with 'shared' option",
+                    field.name()
+                ),
+            ));
+        }
+        if let Some(field) = args.fields.iter().find(|f| f.deref) {
+            // Getters already return references to the inner values of `Arc` with the option
+            return Err(Error::new_spanned(
+                field.name(),
+                format!(
+                    "Common field '{}' with #[deref] cannot be used with 'shared' option",
+                    field.name()
+                ),
+            ));
+        }
+        // Values of common fields are shared via Arc. Getters return references to the inner values
+        for field in args.fields.iter_mut() {
+            let ty = &field.field.ty;
+            field.field.ty = syn::parse_quote!(::std::sync::Arc<#ty>);
+            field.deref = true;
+            // Default values are given as `T` so they are also put into `Arc`
+            if let Some(ref default) = field.default {
+                field.default = Some(syn::parse_quote!(::std::sync::Arc::new(#default)));
+            }
+        }
+    }
 
-    let impl_accessors = generate_accessors(&shared, &input, input.ident.clone());
-    let expanded_enum = expand_shared_fields(&shared, input);
-    let tokens = quote! {
+    let impl_accessors = if args.no_accessors {
+        TokenStream2::new()
+    } else {
+        let accessors = generate_accessors(&args, &input, input.ident.clone())?;
+        let pin_guards = generate_pin_guards(&args, &input);
+        quote!(#accessors #pin_guards)
+    };
+    let common_struct = if args.common_struct {
+        generate_common_struct(&args, &input)?
+    } else {
+        TokenStream2::new()
+    };
+    let partial_eq = if args.partial_eq {
+        generate_partial_eq(&args, &input)
+    } else {
+        TokenStream2::new()
+    };
+    let hash = if args.hash {
+        generate_hash(&args, &input)
+    } else {
+        TokenStream2::new()
+    };
+    let ord = match args.fields.iter().find(|f| f.ord_key) {
+        Some(field) => generate_ord(field, &input),
+        None => TokenStream2::new(),
+    };
+    let display = match args.fields.iter().find(|f| f.display) {
+        Some(field) => generate_display(field, &input),
+        None => TokenStream2::new(),
+    };
+    let kind_enum = if let Some(ref kind) = args.kind {
+        generate_kind_enum(&args, kind, &input)?
+    } else {
+        TokenStream2::new()
+    };
+    let as_ref = generate_as_ref(&args, &input)?;
+    let views = if args.view {
+        generate_views(&args, &input)?
+    } else {
+        TokenStream2::new()
+    };
+    let builders = if args.builder {
+        generate_builders(&args, &input)?
+    } else {
+        TokenStream2::new()
+    };
+    let transitions = if args.transition {
+        generate_transitions(&args, &input)?
+    } else {
+        TokenStream2::new()
+    };
+    let serde = if args.serde_flatten {
+        generate_serde(&args, &input)?
+    } else {
+        TokenStream2::new()
+    };
+    let override_checks = if args.allow_override {
+        generate_override_checks(&args, &input)?
+    } else {
+        TokenStream2::new()
+    };
+    let expanded_enum = expand_shared_fields(&args, input)?;
+    let default = match args.default {
+        Some(ref variant) => generate_default(&args, variant, &expanded_enum)?,
+        None => TokenStream2::new(),
+    };
+    let maps = if args.map {
+        generate_maps(&args, &expanded_enum)?
+    } else {
+        TokenStream2::new()
+    };
+    let constructors = if args.constructor {
+        generate_constructors(&args, &expanded_enum)?
+    } else {
+        TokenStream2::new()
+    };
+    Ok(quote! {
         #expanded_enum
         #impl_accessors
-    };
+        #default
+        #common_struct
+        #kind_enum
+        #builders
+        #transitions
+        #maps
+        #constructors
+        #as_ref
+        #views
+        #partial_eq
+        #hash
+        #ord
+        #display
+        #serde
+        #override_checks
+    })
+}
 
-    tokens.into()
+// Derives put above #[common_fields] are expanded before common fields are injected, and their
+// implementations fail to compile with errors which don't explain the cause. The attribute macro cannot see
+// such derives, but helper attributes of the derives such as `#[default]` remain in the enum. When a helper
+// attribute is found without its derive below the attribute, the derive must be put above it.
+fn check_derive_order(args: &Args, input: &DeriveInput) -> Result<()> {
+    let derives = input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("derive") || attr.path.is_ident("cfg_attr"))
+        .map(|attr| attr.tts.to_string())
+        .collect::<Vec<_>>();
+    let mut helpers = vec![("default", &["Default"][..])];
+    if !args.serde_flatten {
+        // Helper types generated with `serde_flatten` option derive serde traits by themselves
+        helpers.push(("serde", &["Serialize", "Deserialize"][..]));
+    }
+    let variants = enum_variants(input)?;
+    let attrs = input
+        .attrs
+        .iter()
+        .chain(variants.iter().flat_map(|variant| {
+            let fields = variant.fields.iter().flat_map(|field| field.attrs.iter());
+            variant.attrs.iter().chain(fields)
+        }));
+    for attr in attrs {
+        for (helper, traits) in helpers.iter() {
+            if !attr.path.is_ident(helper) {
+                continue;
+            }
+            let derived = derives.iter().any(|tokens| {
+                traits.iter().any(|t| {
+                    tokens
+                        .split(|c: char| !c.is_alphanumeric())
+                        .any(|w| w == *t)
+                })
+            });
+            if !derived {
+                return Err(Error::new_spanned(
+                    attr,
+                    format!(
+                        "#[{}] is found but #[derive({})] is not put below #[diff_enum::common_fields]. A derive put above the attribute is expanded before common fields are injected so it causes compilation errors. Please put the derive below #[diff_enum::common_fields]",
+                        helper,
+                        traits.join(", "),
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(())
 }
 
-fn parse_shared_fields(attr: TokenStream) -> FieldsNamed {
-    use proc_macro::{Delimiter, Group, TokenTree};
-    let braced = TokenStream::from(TokenTree::Group(Group::new(Delimiter::Brace, attr)));
-    match syn::parse(braced) {
-        Ok(fields) => fields,
-        Err(err) => panic!(
-            "Cannot parse fields in attributes at #[diff_enum::common_fields]: {}",
-            err
-        ),
+// Other #[common_fields] attributes stacked under the expanded one are removed from the enum and their
+// arguments are concatenated to the argument of the expanded one so that all of them are processed at
+// once in order.
+fn accumulate_stacked_args(attr: TokenStream2, input: &mut DeriveInput) -> Result<TokenStream2> {
+    // The same attribute put twice by mistake would cause errors of duplicate common fields. It is
+    // reported separately with its cause
+    let mut seen = vec![attr.to_string()];
+    let mut args = attr;
+    let mut attrs = Vec::with_capacity(input.attrs.len());
+    for a in input.attrs.drain(..) {
+        if !is_common_fields_attr(&a) {
+            attrs.push(a);
+            continue;
+        }
+        // Remove the delimiter of the argument such as `{ ... }` or `( ... )`
+        let mut tts = a.tts.clone().into_iter().collect::<Vec<_>>();
+        let stacked = match tts.pop() {
+            Some(TokenTree::Group(ref g)) if tts.is_empty() => g.stream(),
+            _ => {
+                return Err(Error::new_spanned(
+                    &a,
+                    "Stacked #[diff_enum::common_fields] must have its arguments in the form of #[common_fields(...)] or #[common_fields { ... }]",
+                ))
+            }
+        };
+        let repr = stacked.to_string();
+        if seen.contains(&repr) {
+            return Err(Error::new_spanned(
+                &a,
+                "#[diff_enum::common_fields] with the same arguments is put to the enum more than once. Please remove the duplicate attribute",
+            ));
+        }
+        seen.push(repr);
+        let ends_with_comma = match args.clone().into_iter().last() {
+            Some(TokenTree::Punct(ref p)) => p.as_char() == ',',
+            Some(_) => false,
+            None => true,
+        };
+        if !ends_with_comma {
+            args.extend(quote!(,));
+        }
+        args.extend(stacked);
     }
+    input.attrs = attrs;
+    Ok(args)
 }
 
-fn expand_shared_fields(shared: &FieldsNamed, mut input: DeriveInput) -> TokenStream2 {
-    let mut enum_ = match input.data {
-        Data::Enum(e) => e,
-        _ => panic!("#[diff_enum::common_fields] can be set at only enum"),
-    };
+/// Arguments of `#[common_fields]`. Options and common fields are separated by commas, and common
+/// fields may be grouped with braces. An identifier not followed by `:` is an option, which is a flag
+/// such as `prepend`, a string value such as `vis = "pub"` or parenthesized arguments such as `kind(...)`.
+///
+/// ```ignore
+/// #[common_fields(prepend, vis = "pub", { x: i32, y: u32 })]
+/// ```
+struct Args {
+    prepend: bool,
+    vis: Option<Visibility>,
+    copy: bool,
+    common_struct: bool,
+    kind: Option<KindOptions>,
+    into: bool,
+    partial_eq: bool,
+    hash: bool,
+    allow_override: bool,
+    builder: bool,
+    transition: bool,
+    map: bool,
+    constructor: bool,
+    serde_flatten: bool,
+    shared: bool,
+    common_refs: bool,
+    must_use: bool,
+    warn_unused: bool,
+    // Prefix prepended to names of all common fields such as `common_` for `common_id`
+    prefix: Option<LitStr>,
+    case: NameCase,
+    // Which of getter `x()`, setter `set_x()` and mutable getter `x_mut()` are defined
+    getter: bool,
+    setter: bool,
+    mut_getter: bool,
+    tuple: bool,
+    view: bool,
+    no_accessors: bool,
+    // `const` is a keyword so the field is named differently
+    const_fn: bool,
+    default: Option<Ident>,
+    where_clause: Option<WhereClause>,
+    // Attribute to control inlining of accessors given by `inline = "..."`
+    inline: TokenStream2,
+    trait_name: Option<Ident>,
+    impl_trait: Option<Path>,
+    fields: Vec<CommonField>,
+    // Options put to each variant of the enum with #[common_fields(...)]. This is not a part of the
+    // attribute argument and filled after parsing the enum definition.
+    variants: Vec<VariantOptions>,
+}
 
-    for variant in enum_.variants.iter_mut() {
-        match variant.fields {
-            Fields::Named(ref mut f) => {
-                for shared_field in shared.named.iter() {
-                    f.named.push(shared_field.clone());
-                }
+impl Args {
+    fn has_field(&self, variant: usize, field: &CommonField) -> bool {
+        !self.variants[variant]
+            .skip
+            .iter()
+            .any(|ident| ident == field.name())
+    }
+
+    // Member to access the common field in the variant. It is an index for tuple variants.
+    fn member(&self, variant: usize, field: &CommonField) -> TokenStream2 {
+        match self.variants[variant].tuple_offset {
+            Some(offset) => {
+                let pos = self
+                    .fields
+                    .iter()
+                    .take_while(|f| f.name() != field.name())
+                    .filter(|f| self.has_field(variant, f))
+                    .count();
+                let index = syn::Index::from(offset + pos);
+                quote!(#index)
             }
-            Fields::Unnamed(_) => panic!(
-                "#[diff_enum::common_fields] cannot mix named fields with unnamed fields at enum variant {}",
-                variant.ident.to_string()
-            ),
-            Fields::Unit => {
-                variant.fields = Fields::Named(shared.clone());
+            None => {
+                let name = field.name();
+                quote!(#name)
             }
         }
     }
 
-    input.data = Data::Enum(enum_);
-    quote!(#input)
+    // Field pattern to bind the common field in the variant to the variable with the same name as the
+    // field such as `ref x` or `2: ref x`
+    fn field_pattern(
+        &self,
+        variant: usize,
+        field: &CommonField,
+        binding: TokenStream2,
+    ) -> TokenStream2 {
+        let name = field.name();
+        if self.variants[variant].tuple_offset.is_some() {
+            let member = self.member(variant, field);
+            quote!(#member: #binding #name)
+        } else {
+            quote!(#binding #name)
+        }
+    }
+
+    fn fields_named(&self, variant: usize) -> FieldsNamed {
+        FieldsNamed {
+            brace_token: token::Brace::default(),
+            named: self
+                .fields
+                .iter()
+                .filter(|field| self.has_field(variant, field))
+                .map(CommonField::variant_field)
+                .collect(),
+        }
+    }
+
+    fn fields_unnamed(&self, variant: usize) -> Vec<Field> {
+        self.fields
+            .iter()
+            .filter(|field| self.has_field(variant, field))
+            .map(|field| {
+                let mut field = field.variant_field();
+                field.ident = None;
+                field.colon_token = None;
+                field
+            })
+            .collect()
+    }
+
+    // Parses an option following its name such as `= "pub"` for `vis = "pub"`. Errors are reported
+    // with the option name so that they are distinguished from errors of common fields.
+    fn parse_option(&mut self, option: Ident, input: ParseStream) -> Result<()> {
+        let name = option.to_string();
+        match name.as_str() {
+            "prepend" => self.prepend = true,
+            "copy" => self.copy = true,
+            "common_struct" => self.common_struct = true,
+            "kind" => self.kind = Some(input.parse()?),
+            "into" => self.into = true,
+            "partial_eq" => self.partial_eq = true,
+            "hash" => self.hash = true,
+            "allow_override" => self.allow_override = true,
+            "builder" => self.builder = true,
+            "transition" => self.transition = true,
+            "map" => self.map = true,
+            "constructor" => self.constructor = true,
+            "serde_flatten" => self.serde_flatten = true,
+            "shared" => self.shared = true,
+            "common_refs" => self.common_refs = true,
+            "must_use" => self.must_use = true,
+            "warn_unused" => self.warn_unused = true,
+            "tuple" => self.tuple = true,
+            "view" => self.view = true,
+            "no_accessors" => self.no_accessors = true,
+            "inline" => {
+                let value = parse_option_value(&name, input)?;
+                self.inline = match value.value().as_str() {
+                    "always" => quote!(#[inline(always)]),
+                    "never" => quote!(#[inline(never)]),
+                    "hint" => quote!(#[inline]),
+                    "none" => TokenStream2::new(),
+                    v => {
+                        return Err(Error::new_spanned(
+                            &value,
+                            format!(
+                                "Unknown value '{}' for inline option. It must be one of \"always\", \"never\", \"hint\" or \"none\"",
+                                v
+                            ),
+                        ))
+                    }
+                };
+            }
+            "default" => {
+                let variant = parse_option_value(&name, input)?;
+                self.default = Some(variant.parse()?);
+            }
+            "impl_trait" => {
+                let path = parse_option_value(&name, input)?;
+                self.impl_trait = Some(path.parse()?);
+            }
+            "vis" => {
+                let vis = parse_option_value(&name, input)?;
+                self.vis = Some(vis.parse()?);
+            }
+            "prefix" => self.prefix = Some(parse_option_value(&name, input)?),
+            "case" => {
+                let value = parse_option_value(&name, input)?;
+                self.case = match value.value().as_str() {
+                    "snake" => NameCase::Snake,
+                    "lower" => NameCase::Lower,
+                    v => {
+                        return Err(Error::new_spanned(
+                            &value,
+                            format!(
+                                "Unknown value '{}' for case option. It must be one of \"snake\" or \"lower\"",
+                                v
+                            ),
+                        ))
+                    }
+                };
+            }
+            "accessors" => {
+                let value = parse_option_value(&name, input)?;
+                self.getter = false;
+                self.setter = false;
+                self.mut_getter = false;
+                for kind in value.value().split(',').map(str::trim) {
+                    match kind {
+                        "get" => self.getter = true,
+                        "set" => self.setter = true,
+                        "mut" => self.mut_getter = true,
+                        k => {
+                            return Err(Error::new_spanned(
+                                &value,
+                                format!(
+                                    "Unknown accessor '{}' for accessors option. It must be a comma-separated list of \"get\", \"set\" and \"mut\" such as \"get,mut\"",
+                                    k
+                                ),
+                            ))
+                        }
+                    }
+                }
+            }
+            _ => {
+                return Err(Error::new_spanned(
+                    &option,
+                    format!(
+                        "Unknown option '{}'. If '{}' is a common field, its type is missing. Please specify it in the form of `{}: Type`",
+                        option, option, option
+                    ),
+                ))
+            }
+        }
+        Ok(())
+    }
 }
 
-fn generate_accessors(shared: &FieldsNamed, input: &DeriveInput, enum_name: Ident) -> TokenStream2 {
-    let variants = match input.data {
-        Data::Enum(ref e) => &e.variants,
-        _ => panic!("#[diff_enum::common_fields] can be set at only enum"),
-    };
+impl Parse for Args {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut args = Args {
+            prepend: false,
+            vis: None,
+            copy: false,
+            common_struct: false,
+            kind: None,
+            into: false,
+            partial_eq: false,
+            hash: false,
+            allow_override: false,
+            builder: false,
+            transition: false,
+            map: false,
+            constructor: false,
+            serde_flatten: false,
+            shared: false,
+            common_refs: false,
+            must_use: false,
+            warn_unused: false,
+            prefix: None,
+            case: NameCase::Snake,
+            getter: true,
+            setter: false,
+            mut_getter: false,
+            tuple: false,
+            view: false,
+            no_accessors: false,
+            const_fn: false,
+            default: None,
+            where_clause: None,
+            inline: quote!(#[inline]),
+            trait_name: None,
+            impl_trait: None,
+            fields: vec![],
+            variants: vec![],
+        };
 
-    let accessors = shared.named.iter().map(|field| {
-        let field_name = &field.ident;
-        let ty = &field.ty;
-        let arms = variants.iter().map(|variant| {
-            let ident = &variant.ident;
-            quote! {
-                #enum_name::#ident{ref #field_name, ..} => #field_name,
+        while !input.is_empty() {
+            if input.peek(token::Brace) {
+                let content;
+                braced!(content in input);
+                let fields: Punctuated<CommonField, Token![,]> = content
+                    .parse_terminated(parse_common_field)
+                    .map_err(field_error)?;
+                args.fields.extend(fields);
+            } else if input.peek(Token![where]) {
+                let where_clause: WhereClause = input.parse()?;
+                // The comma after the last predicate is consumed by the parser
+                let consumed_comma = where_clause.predicates.trailing_punct();
+                args.where_clause = Some(where_clause);
+                if consumed_comma {
+                    continue;
+                }
+            } else if input.peek(Token![const]) {
+                // `const` is a keyword so it is not parsed as Ident
+                input.parse::<Token![const]>()?;
+                args.const_fn = true;
+            } else if input.peek(Token![trait]) {
+                // `trait` is a keyword so it is not parsed as Ident
+                input.parse::<Token![trait]>()?;
+                let name = parse_option_value("trait", input)?;
+                args.trait_name = Some(name.parse()?);
+            } else if input.peek(Ident) && !input.peek2(Token![:]) {
+                let option: Ident = input.parse()?;
+                args.parse_option(option, input)?;
+            } else {
+                let field = input.call(parse_common_field).map_err(field_error)?;
+                args.fields.push(field);
             }
-        });
-        quote! {
-            #[inline]
-            #[allow(dead_code)]
-            #[allow(missing_docs)]
-            pub fn #field_name (&self) -> &#ty {
-                match self {
-                    #( #arms )*
+
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<Token![,]>().map_err(field_error)?;
+        }
+
+        let mut names = HashSet::new();
+        for field in args.fields.iter() {
+            let name = field.name();
+            if !names.insert(name.to_string()) {
+                return Err(Error::new_spanned(
+                    name,
+                    format!("Common field '{}' is declared more than once", name),
+                ));
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+// Parses a common field in the form of `name: Type` such as `x: i32`. Unlike Rust syntax, a default
+// value can follow the type such as `x: i32 = 42`. A field without type is reported with a dedicated
+// error since the error from syn ("expected `:`") is not helpful.
+fn parse_common_field(input: ParseStream) -> Result<CommonField> {
+    let fork = input.fork();
+    fork.call(Attribute::parse_outer)?;
+    fork.parse::<Visibility>()?;
+    if fork.peek(Ident) && !fork.peek2(Token![:]) {
+        let name: Ident = fork.parse()?;
+        return Err(Error::new_spanned(
+            &name,
+            format!(
+                "Type of common field '{}' is missing. Please specify it in the form of `{}: Type`",
+                name, name
+            ),
+        ));
+    }
+    let mut field = CommonField::new(input.call(Field::parse_named)?)?;
+    if input.peek(Token![=]) {
+        input.parse::<Token![=]>()?;
+        field.default = Some(input.parse()?);
+    }
+    Ok(field)
+}
+
+/// Case of variant names in method names given by `case = "..."`
+#[derive(Clone, Copy)]
+enum NameCase {
+    // `GitHub` is converted into `git_hub`
+    Snake,
+    // `GitHub` is converted into `github`
+    Lower,
+}
+
+/// Options of the kind enum given by `kind(...)` such as `kind(vis = "pub", derive(Hash), repr(u8))`
+#[derive(Default)]
+struct KindOptions {
+    vis: Option<Visibility>,
+    derives: Vec<Path>,
+    repr: Option<Ident>,
+}
+
+impl Parse for KindOptions {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut opts = KindOptions::default();
+        if !input.peek(token::Paren) {
+            return Ok(opts);
+        }
+        let content;
+        syn::parenthesized!(content in input);
+        while !content.is_empty() {
+            let option: Ident = content.parse()?;
+            match option.to_string().as_str() {
+                "vis" => {
+                    content.parse::<Token![=]>()?;
+                    let vis: LitStr = content.parse()?;
+                    opts.vis = Some(vis.parse()?);
+                }
+                "derive" => {
+                    let derives;
+                    syn::parenthesized!(derives in content);
+                    let derives: Punctuated<Path, Token![,]> =
+                        derives.parse_terminated(Path::parse_mod_style)?;
+                    opts.derives.extend(derives);
+                }
+                "repr" => {
+                    let repr;
+                    syn::parenthesized!(repr in content);
+                    opts.repr = Some(repr.parse()?);
                 }
+                _ => {
+                    return Err(Error::new_spanned(
+                        &option,
+                        format!("Unknown option '{}' for kind option", option),
+                    ))
+                }
+            }
+            if content.is_empty() {
+                break;
             }
+            content.parse::<Token![,]>()?;
         }
-    });
+        Ok(opts)
+    }
+}
 
-    quote! {
-        impl #enum_name {
-            #( #accessors )*
+// Parses `= "..."` of the option such as `vis = "pub"`
+fn parse_option_value(option: &str, input: ParseStream) -> Result<LitStr> {
+    let value = input
+        .parse::<Token![=]>()
+        .and_then(|_| input.parse::<LitStr>());
+    value.map_err(|err| {
+        Error::new(
+            err.span(),
+            format!(
+                "Option '{}' requires a string value in the form of `{} = \"...\"`",
+                option, option
+            ),
+        )
+    })
+}
+
+// Errors while parsing common fields are reported with context since they are based on Rust syntax
+fn field_error(err: Error) -> Error {
+    Error::new(
+        err.span(),
+        format!(
+            "Cannot parse fields in attributes at #[diff_enum::common_fields]: {}",
+            err
+        ),
+    )
+}
+
+fn parse_args(attr: TokenStream2) -> Result<Args> {
+    syn::parse2(attr)
+}
+
+/// A common field and its options given by attributes such as `#[variant_optional]`. The attributes
+/// for options are removed from the field.
+struct CommonField {
+    field: Field,
+    variant_optional: bool,
+    // Name of the getter given by #[accessor(name = "...")]
+    accessor: Option<Ident>,
+    // Target types of AsRef given by #[as_ref(...)]. None means the type of the field
+    as_ref: Vec<Option<Type>>,
+    // Pin projection accessor is generated for the field with #[pin]
+    pin: bool,
+    // Getter returns a reference to the deref target for the field with #[deref]
+    deref: bool,
+    // Getter returns `Cow` of the deref target for the field with #[cow]
+    cow: bool,
+    // `PartialOrd` and `Ord` compare values by the field with #[ord_key]
+    ord_key: bool,
+    // `Display` writes the field with #[display]
+    display: bool,
+    // Groups given by #[group(...)]. Only variants joining one of the groups with #[groups(...)] have
+    // the field
+    groups: Vec<Ident>,
+    // Default value given by `x: T = expr`. It is used by builders and the `Default` implementation
+    default: Option<Expr>,
+}
+
+impl CommonField {
+    fn new(mut field: Field) -> Result<Self> {
+        let mut variant_optional = false;
+        let mut accessor = None;
+        let mut as_ref = vec![];
+        let mut pin = false;
+        let mut deref = false;
+        let mut cow = false;
+        let mut ord_key = false;
+        let mut display = false;
+        let mut groups = vec![];
+        let mut attrs = Vec::with_capacity(field.attrs.len());
+        for attr in field.attrs.into_iter() {
+            if attr.path.is_ident("variant_optional") {
+                if !attr.tts.is_empty() {
+                    return Err(Error::new_spanned(
+                        &attr.tts,
+                        "#[variant_optional] does not take any argument",
+                    ));
+                }
+                variant_optional = true;
+            } else if attr.path.is_ident("accessor") {
+                accessor = Some(parse_accessor_attr(&attr)?);
+            } else if attr.path.is_ident("pin") {
+                if !attr.tts.is_empty() {
+                    return Err(Error::new_spanned(
+                        &attr.tts,
+                        "#[pin] does not take any argument",
+                    ));
+                }
+                pin = true;
+            } else if attr.path.is_ident("deref") {
+                if !attr.tts.is_empty() {
+                    return Err(Error::new_spanned(
+                        &attr.tts,
+                        "#[deref] does not take any argument",
+                    ));
+                }
+                deref = true;
+            } else if attr.path.is_ident("cow") {
+                if !attr.tts.is_empty() {
+                    return Err(Error::new_spanned(
+                        &attr.tts,
+                        "#[cow] does not take any argument",
+                    ));
+                }
+                cow = true;
+            } else if attr.path.is_ident("ord_key") {
+                if !attr.tts.is_empty() {
+                    return Err(Error::new_spanned(
+                        &attr.tts,
+                        "#[ord_key] does not take any argument",
+                    ));
+                }
+                ord_key = true;
+            } else if attr.path.is_ident("display") {
+                if !attr.tts.is_empty() {
+                    return Err(Error::new_spanned(
+                        &attr.tts,
+                        "#[display] does not take any argument",
+                    ));
+                }
+                display = true;
+            } else if attr.path.is_ident("group") {
+                // Variants which don't join the group skip the field
+                groups.extend(parse_group_attr(&attr)?);
+                variant_optional = true;
+            } else if attr.path.is_ident("as_ref") {
+                if attr.tts.is_empty() {
+                    as_ref.push(None);
+                } else {
+                    let parser = |input: ParseStream| {
+                        let content;
+                        syn::parenthesized!(content in input);
+                        content.parse_terminated::<Type, Token![,]>(Type::parse)
+                    };
+                    as_ref.extend(parser.parse2(attr.tts.clone())?.into_iter().map(Some));
+                }
+            } else {
+                attrs.push(attr);
+            }
         }
+        field.attrs = attrs;
+        Ok(CommonField {
+            field,
+            variant_optional,
+            accessor,
+            as_ref,
+            pin,
+            deref,
+            cow,
+            ord_key,
+            display,
+            groups,
+            default: None,
+        })
+    }
+
+    // The field injected into enum variants. Fields of variants cannot have visibility since they are
+    // always as visible as the enum. Visibility of the common field is only used by the common struct
+    fn variant_field(&self) -> Field {
+        let mut field = self.field.clone();
+        field.vis = Visibility::Inherited;
+        field
+    }
+
+    // Name of the getter method. Names of other accessors are also derived from this
+    fn accessor_name(&self) -> &Ident {
+        self.accessor.as_ref().unwrap_or_else(|| self.name())
+    }
+
+    fn name(&self) -> &Ident {
+        self.field.ident.as_ref().unwrap()
+    }
+
+    // Doc comments of the field. They are forwarded to the getter method.
+    fn docs(&self) -> impl Iterator<Item = &Attribute> {
+        self.field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("doc"))
+    }
+
+    // #[cfg(...)] attributes of the field. They are forwarded to all methods which use the field
+    // since the methods cannot be compiled when the field does not exist.
+    fn cfgs(&self) -> impl Iterator<Item = &Attribute> {
+        self.field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("cfg"))
+    }
+
+    // #[deprecated] attributes of the field. They are forwarded to accessors of the field
+    fn deprecations(&self) -> impl Iterator<Item = &Attribute> {
+        self.field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("deprecated"))
+    }
+}
+
+// Parses #[accessor(name = "...")] and returns the name
+fn parse_accessor_attr(attr: &Attribute) -> Result<Ident> {
+    let mut name = None;
+    match attr.parse_meta()? {
+        Meta::List(list) => {
+            for nested in list.nested.iter() {
+                match nested {
+                    NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                        ident,
+                        lit: Lit::Str(lit),
+                        ..
+                    })) if ident == "name" => name = Some(lit.parse()?),
+                    _ => return Err(Error::new_spanned(
+                        nested,
+                        "Unknown argument for #[accessor(...)]. Only `name = \"...\"` is available",
+                    )),
+                }
+            }
+        }
+        meta => {
+            return Err(Error::new_spanned(
+                meta,
+                "#[accessor] must be in the form of #[accessor(name = \"...\")]",
+            ))
+        }
+    }
+    name.ok_or_else(|| Error::new_spanned(attr, "name is not set to #[accessor(...)]"))
+}
+
+// Parses group names of #[group(a, b)] at common fields and #[groups(a, b)] at variants
+fn parse_group_attr(attr: &Attribute) -> Result<Vec<Ident>> {
+    let parser = |input: ParseStream| {
+        let content;
+        syn::parenthesized!(content in input);
+        content.parse_terminated::<Ident, Token![,]>(Ident::parse)
+    };
+    let name = &attr.path;
+    let groups = parser.parse2(attr.tts.clone()).map_err(|_| {
+        Error::new_spanned(
+            attr,
+            format!(
+                "#[{}] must be in the form of #[{}(name, ...)]",
+                quote!(#name),
+                quote!(#name)
+            ),
+        )
+    })?;
+    if groups.is_empty() {
+        return Err(Error::new_spanned(
+            attr,
+            format!("No group is given to #[{}(...)]", quote!(#name)),
+        ));
+    }
+    Ok(groups.into_iter().collect())
+}
+
+/// Options of each variant given by `#[common_fields(...)]` attribute at the variant.
+///
+/// ```ignore
+/// #[common_fields(skip(x, y))]
+/// A { ... },
+/// ```
+#[derive(Default)]
+struct VariantOptions {
+    skip: Vec<Ident>,
+    // `skip` without field names skips all common fields. This is resolved into `skip` after parsing
+    skip_all: Option<Ident>,
+    // Index of the first common field when common fields are injected into the variant as positional
+    // fields with `tuple` option. This is not given by the attribute.
+    tuple_offset: Option<usize>,
+}
+
+impl Parse for VariantOptions {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut opts = VariantOptions::default();
+        let content;
+        syn::parenthesized!(content in input);
+        while !content.is_empty() {
+            let option: Ident = content.parse()?;
+            match option.to_string().as_str() {
+                "skip" if content.peek(token::Paren) => {
+                    let fields;
+                    syn::parenthesized!(fields in content);
+                    let fields: Punctuated<Ident, Token![,]> =
+                        fields.parse_terminated(Ident::parse)?;
+                    opts.skip.extend(fields);
+                }
+                "skip" => opts.skip_all = Some(option),
+                _ => {
+                    return Err(Error::new_spanned(
+                        &option,
+                        format!("Unknown option '{}' for enum variant", option),
+                    ))
+                }
+            }
+            if content.is_empty() {
+                break;
+            }
+            content.parse::<Token![,]>()?;
+        }
+        Ok(opts)
+    }
+}
+
+fn is_common_fields_attr(attr: &Attribute) -> bool {
+    attr.path
+        .segments
+        .last()
+        .map_or(false, |seg| seg.value().ident == "common_fields")
+}
+
+// Parses and removes #[common_fields(...)] attributes at enum variants
+fn parse_variant_options(args: &Args, input: &mut DeriveInput) -> Result<Vec<VariantOptions>> {
+    let variants = match input.data {
+        Data::Enum(ref mut e) => &mut e.variants,
+        _ => return Ok(vec![]),
+    };
+
+    let mut parsed = Vec::with_capacity(variants.len());
+    for variant in variants.iter_mut() {
+        let mut opts = VariantOptions::default();
+        let mut groups = vec![];
+        let mut attrs = Vec::with_capacity(variant.attrs.len());
+        for attr in variant.attrs.drain(..) {
+            if attr.path.is_ident("groups") {
+                groups.extend(parse_group_attr(&attr)?);
+                continue;
+            }
+            if !is_common_fields_attr(&attr) {
+                attrs.push(attr);
+                continue;
+            }
+            let o: VariantOptions = syn::parse2(attr.tts.clone())?;
+            opts.skip.extend(o.skip);
+            if o.skip_all.is_some() {
+                opts.skip_all = o.skip_all;
+            }
+        }
+        variant.attrs = attrs;
+
+        if let Some(ref skip) = opts.skip_all {
+            // Accessors of all common fields must return Option for this variant
+            if let Some(field) = args.fields.iter().find(|f| !f.variant_optional) {
+                return Err(Error::new_spanned(
+                    skip,
+                    format!(
+                        "Common field '{}' cannot be skipped at enum variant {} since it is not marked with #[variant_optional]. All common fields must be marked with #[variant_optional] to skip them with #[common_fields(skip)]",
+                        field.name(),
+                        variant.ident
+                    ),
+                ));
+            }
+            opts.skip = args.fields.iter().map(|f| f.name().clone()).collect();
+        }
+
+        for group in groups.iter() {
+            if !args.fields.iter().any(|f| f.groups.contains(group)) {
+                return Err(Error::new_spanned(
+                    group,
+                    format!(
+                        "Group '{}' at enum variant {} is not given to any common field with #[group(...)]",
+                        group, variant.ident
+                    ),
+                ));
+            }
+        }
+        // Fields in groups are skipped unless the variant joins one of the groups
+        for field in args.fields.iter().filter(|f| !f.groups.is_empty()) {
+            let joined = field.groups.iter().any(|g| groups.contains(g));
+            if !joined && !opts.skip.contains(field.name()) {
+                opts.skip.push(field.name().clone());
+            }
+        }
+
+        for skipped in opts.skip.iter() {
+            match args.fields.iter().find(|f| f.name() == skipped) {
+                Some(field) if field.variant_optional => {}
+                Some(_) => {
+                    return Err(Error::new_spanned(
+                        skipped,
+                        format!(
+                            "Common field '{}' cannot be skipped at enum variant {} since it is not marked with #[variant_optional]",
+                            skipped, variant.ident
+                        ),
+                    ))
+                }
+                None => {
+                    return Err(Error::new_spanned(
+                        skipped,
+                        format!(
+                            "'{}' is not a common field set to #[diff_enum::common_fields]",
+                            skipped
+                        ),
+                    ))
+                }
+            }
+        }
+
+        if args.tuple {
+            let has_fields = args.fields.iter().any(|f| !opts.skip.contains(f.name()));
+            opts.tuple_offset = match variant.fields {
+                Fields::Unnamed(ref f) if !args.prepend => Some(f.unnamed.len()),
+                Fields::Unnamed(_) => Some(0),
+                Fields::Unit if has_fields => Some(0),
+                _ => None,
+            };
+        }
+        if opts.tuple_offset.is_some() {
+            // Indices of positional fields cannot be determined when some field is removed by #[cfg]
+            let cfg = args
+                .fields
+                .iter()
+                .filter(|f| !opts.skip.contains(f.name()))
+                .find(|f| f.cfgs().next().is_some());
+            if let Some(field) = cfg {
+                return Err(Error::new_spanned(
+                    field.name(),
+                    format!(
+                        "Common field '{}' with #[cfg] cannot be injected into tuple variant {}",
+                        field.name(),
+                        variant.ident
+                    ),
+                ));
+            }
+        }
+        parsed.push(opts);
+    }
+    Ok(parsed)
+}
+
+fn enum_variants(input: &DeriveInput) -> Result<&Punctuated<Variant, Token![,]>> {
+    match input.data {
+        Data::Enum(ref e) => Ok(&e.variants),
+        _ => Err(Error::new_spanned(
+            &input.ident,
+            "#[diff_enum::common_fields] can be set at only enum",
+        )),
+    }
+}
+
+// #[cfg(...)] attributes of the variant. They are forwarded to all match arms and items generated for the
+// variant so that a feature-gated variant works as expected
+fn variant_cfgs(variant: &Variant) -> Vec<&Attribute> {
+    variant
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("cfg"))
+        .collect()
+}
+
+fn expand_shared_fields(args: &Args, mut input: DeriveInput) -> Result<DeriveInput> {
+    let mut enum_ = match input.data {
+        Data::Enum(e) => e,
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "#[diff_enum::common_fields] can be set at only enum",
+            ))
+        }
+    };
+
+    // Explicit discriminants are only allowed on enums with fields when the representation is specified
+    let has_repr = input.attrs.iter().any(|attr| attr.path.is_ident("repr"));
+    if !has_repr {
+        if let Some((_, discriminant)) = enum_.variants.iter().find_map(|v| v.discriminant.as_ref())
+        {
+            return Err(Error::new_spanned(
+                discriminant,
+                "Explicit discriminant cannot be put to enum variants with #[diff_enum::common_fields] since common fields are injected into the variants. Please remove the discriminants or specify the representation such as #[repr(u8)]",
+            ));
+        }
+    }
+
+    for (idx, variant) in enum_.variants.iter_mut().enumerate() {
+        match variant.fields {
+            Fields::Named(ref mut f) => {
+                let mut injected = vec![];
+                for shared_field in args.fields.iter() {
+                    if !args.has_field(idx, shared_field) {
+                        continue;
+                    }
+                    let name = shared_field.name();
+                    if f.named.iter().any(|f| f.ident.as_ref() == Some(name)) {
+                        if args.allow_override {
+                            // The type of the overriding field is checked by generate_override_checks()
+                            continue;
+                        }
+                        return Err(Error::new_spanned(
+                            name,
+                            format!(
+                                "Common field '{}' set to #[diff_enum::common_fields] is already declared in enum variant {}. Please remove either of them or use allow_override option",
+                                name, variant.ident
+                            ),
+                        ));
+                    }
+                    injected.push(shared_field.variant_field());
+                }
+                if args.prepend {
+                    for (i, shared_field) in injected.into_iter().enumerate() {
+                        f.named.insert(i, shared_field);
+                    }
+                } else {
+                    f.named.extend(injected);
+                }
+            }
+            Fields::Unnamed(ref mut f) if args.tuple => {
+                let injected = args.fields_unnamed(idx);
+                if args.prepend {
+                    for (i, shared_field) in injected.into_iter().enumerate() {
+                        f.unnamed.insert(i, shared_field);
+                    }
+                } else {
+                    f.unnamed.extend(injected);
+                }
+            }
+            Fields::Unnamed(ref f) => {
+                return Err(Error::new_spanned(
+                    f,
+                    format!(
+                        "#[diff_enum::common_fields] cannot mix named fields with unnamed fields at enum variant {}. Please use tuple option to inject common fields as positional fields",
+                        variant.ident
+                    ),
+                ))
+            }
+            Fields::Unit if args.variants[idx].tuple_offset.is_some() => {
+                variant.fields = Fields::Unnamed(FieldsUnnamed {
+                    paren_token: token::Paren::default(),
+                    unnamed: args.fields_unnamed(idx).into_iter().collect(),
+                });
+            }
+            // Only fields are replaced. Attributes on the variant such as #[deprecated] are kept as-is
+            Fields::Unit => {
+                let fields = args.fields_named(idx);
+                if !fields.named.is_empty() {
+                    variant.fields = Fields::Named(fields);
+                }
+            }
+        }
+    }
+
+    input.data = Data::Enum(enum_);
+    Ok(input)
+}
+
+// Wraps a value of common field with Option when the field is marked with #[variant_optional]
+fn optional_value(field: &CommonField, value: Option<TokenStream2>) -> TokenStream2 {
+    match value {
+        Some(v) if field.variant_optional => quote!(::core::option::Option::Some(#v)),
+        Some(v) => v,
+        None => quote!(::core::option::Option::None),
+    }
+}
+
+fn optional_type(field: &CommonField, ty: TokenStream2) -> TokenStream2 {
+    if field.variant_optional {
+        quote!(::core::option::Option<#ty>)
+    } else {
+        ty
+    }
+}
+
+fn generate_accessors(args: &Args, input: &DeriveInput, enum_name: Ident) -> Result<TokenStream2> {
+    let vis = args.vis.as_ref().unwrap_or(&input.vis);
+    // Methods in trait implementation cannot have visibility
+    let accessor_vis = if args.trait_name.is_some() || args.impl_trait.is_some() {
+        Visibility::Inherited
+    } else {
+        vis.clone()
+    };
+    let variants = enum_variants(input)?;
+    check_method_names(args, variants)?;
+    let inline = &args.inline;
+    // Without `#[allow(dead_code)]`, unused accessors are warned so that users can find unnecessary APIs.
+    // The compiler does not report dead code in items spanned by a macro, so the methods and the impl
+    // block need to have spans of the enum definition
+    let allow_dead_code = if args.warn_unused {
+        TokenStream2::new()
+    } else {
+        quote!(#[allow(dead_code)])
+    };
+    // `#[automatically_derived]` at an inherent impl block also suppresses dead code warnings
+    let derived = if args.warn_unused {
+        TokenStream2::new()
+    } else {
+        quote!(#[automatically_derived])
+    };
+    let respan_method = |tokens: TokenStream2, span: Span| {
+        if args.warn_unused {
+            respan(tokens, span)
+        } else {
+            tokens
+        }
+    };
+    let impl_span = if args.warn_unused {
+        enum_name.span()
+    } else {
+        Span::call_site()
+    };
+    let must_use = if args.must_use {
+        quote!(#[must_use])
+    } else {
+        TokenStream2::new()
+    };
+    // Only getters and predicates can be const fn since other methods mutate, move or drop fields
+    let constness = if args.const_fn {
+        quote!(const)
+    } else {
+        TokenStream2::new()
+    };
+
+    // Names of accessors have spans of common fields so that "go to definition" on an accessor of IDE
+    // jumps to the field. Predicates have spans of variants in the same way.
+    let mut accessors = args
+        .fields
+        .iter()
+        .map(|field| {
+            let field_name = field.name();
+            let accessor_name = field.accessor_name();
+            let ty = &field.field.ty;
+            let (ret_ty, ret_val) = if field.cow {
+                (
+                    quote!(::std::borrow::Cow<'_, <#ty as ::core::ops::Deref>::Target>),
+                    quote!(::std::borrow::Cow::Borrowed(::core::ops::Deref::deref(#field_name))),
+                )
+            } else if field.deref && args.shared && args.copy {
+                // With `shared` option, `copy` copies the value in `Arc<T>`
+                (
+                    quote!(<#ty as ::core::ops::Deref>::Target),
+                    quote!(*::core::ops::Deref::deref(#field_name)),
+                )
+            } else if field.deref {
+                (
+                    quote!(&<#ty as ::core::ops::Deref>::Target),
+                    quote!(::core::ops::Deref::deref(#field_name)),
+                )
+            } else if args.copy {
+                (quote!(#ty), quote!(*#field_name))
+            } else {
+                (quote!(&#ty), quote!(#field_name))
+            };
+            // The getter of an optional field with #[cow] and a default value returns the owned default
+            // value for variants which don't have the field instead of `None`
+            let cow_default = if field.cow {
+                field.default.as_ref()
+            } else {
+                None
+            };
+            let ret_ty = if cow_default.is_some() {
+                ret_ty
+            } else {
+                optional_type(field, ret_ty)
+            };
+            // `Deref::deref()` cannot be called in const fn
+            let getter_constness = if field.deref || field.cow {
+                TokenStream2::new()
+            } else {
+                constness.clone()
+            };
+            // Accessors are documented with the variants which have the field. It is appended to doc
+            // comments of the field at the getter
+            let carriers = variants
+                .iter()
+                .enumerate()
+                .filter(|(idx, _)| args.has_field(*idx, field))
+                .map(|(_, variant)| unraw(&variant.ident))
+                .collect::<Vec<_>>();
+            let availability = if carriers.is_empty() {
+                TokenStream2::new()
+            } else {
+                let doc = if carriers.len() == variants.len() {
+                    format!(
+                        "Common field available on all variants: {}.",
+                        carriers.join(", ")
+                    )
+                } else {
+                    format!(
+                        "Common field available on variants: {}.",
+                        carriers.join(", ")
+                    )
+                };
+                quote!(#[doc = #doc])
+            };
+            let docs = field.docs().collect::<Vec<_>>();
+            let getter_docs = if docs.is_empty() {
+                availability.clone()
+            } else {
+                quote! {
+                    #( #docs )*
+                    #[doc = ""]
+                    #availability
+                }
+            };
+            let cfgs = field.cfgs().collect::<Vec<_>>();
+            let cfgs = &cfgs;
+            // #[deprecated] is useless at methods in the implementation of a trait defined by users
+            let deprecated = if args.impl_trait.is_some() {
+                vec![]
+            } else {
+                field.deprecations().collect::<Vec<_>>()
+            };
+            let deprecated = &deprecated;
+            // Arms for variants which don't have the field. They only exist when the field is optional.
+            let missing = |idx: usize, variant: &Variant| {
+                if args.has_field(idx, field) {
+                    None
+                } else {
+                    let ident = &variant.ident;
+                    let variant_cfgs = variant_cfgs(variant);
+                    let none = optional_value(field, None);
+                    Some(quote! {
+                        #( #variant_cfgs )*
+                        #enum_name::#ident{..} => #none,
+                    })
+                }
+            };
+
+            let arms = variants.iter().enumerate().map(|(idx, variant)| {
+                let ident = &variant.ident;
+                let variant_cfgs = variant_cfgs(variant);
+                if let Some(default) = cow_default {
+                    return if args.has_field(idx, field) {
+                        let pat = args.field_pattern(idx, field, quote!(ref));
+                        quote! {
+                            #( #variant_cfgs )*
+                            #enum_name::#ident{#pat, ..} => #ret_val,
+                        }
+                    } else {
+                        quote! {
+                            #( #variant_cfgs )*
+                            #enum_name::#ident{..} => ::std::borrow::Cow::Owned(#default),
+                        }
+                    };
+                }
+                missing(idx, variant).unwrap_or_else(|| {
+                    let val = optional_value(field, Some(ret_val.clone()));
+                    let pat = args.field_pattern(idx, field, quote!(ref));
+                    quote! {
+                        #( #variant_cfgs )*
+                        #enum_name::#ident{#pat, ..} => #val,
+                    }
+                })
+            });
+            let mut_name = Ident::new(
+                &format!("{}_mut", unraw(accessor_name)),
+                accessor_name.span(),
+            );
+            let mut_ty = optional_type(field, quote!(&mut #ty));
+            let mut_arms = variants.iter().enumerate().map(|(idx, variant)| {
+                let ident = &variant.ident;
+                let variant_cfgs = variant_cfgs(variant);
+                missing(idx, variant).unwrap_or_else(|| {
+                    let val = optional_value(field, Some(quote!(#field_name)));
+                    let pat = args.field_pattern(idx, field, quote!(ref mut));
+                    quote! {
+                        #( #variant_cfgs )*
+                        #enum_name::#ident{#pat, ..} => #val,
+                    }
+                })
+            });
+            // Setter and `replace_x()` are not defined for optional fields since some variants don't have
+            // the field. Use `x_mut()` instead.
+            let setter = if field.variant_optional || !args.setter {
+                TokenStream2::new()
+            } else {
+                let setter_name = Ident::new(
+                    &format!("set_{}", unraw(accessor_name)),
+                    accessor_name.span(),
+                );
+                let setter_arms = variants.iter().enumerate().map(|(idx, variant)| {
+                    let ident = &variant.ident;
+                    let variant_cfgs = variant_cfgs(variant);
+                    let member = args.member(idx, field);
+                    quote! {
+                        #( #variant_cfgs )*
+                        #enum_name::#ident{#member: ref mut field, ..} => *field = value,
+                    }
+                });
+                // `replace_x()` is only an inherent method so that other implementations of the trait
+                // don't need to define it
+                let replace = if args.trait_name.is_none() && args.impl_trait.is_none() {
+                    let replace_name = Ident::new(
+                        &format!("replace_{}", unraw(accessor_name)),
+                        accessor_name.span(),
+                    );
+                    let replace_arms = variants.iter().enumerate().map(|(idx, variant)| {
+                        let ident = &variant.ident;
+                        let variant_cfgs = variant_cfgs(variant);
+                        let member = args.member(idx, field);
+                        quote! {
+                            #( #variant_cfgs )*
+                            #enum_name::#ident{#member: ref mut field, ..} => {
+                                ::core::mem::replace(field, value)
+                            }
+                        }
+                    });
+                    quote! {
+                        #inline
+                        #allow_dead_code
+                        #[allow(missing_docs)]
+                        #availability
+                        #( #cfgs )*
+                        #( #deprecated )*
+                        #accessor_vis fn #replace_name (&mut self, value: #ty) -> #ty {
+                            match *self {
+                                #( #replace_arms )*
+                            }
+                        }
+                    }
+                } else {
+                    TokenStream2::new()
+                };
+                quote! {
+                    #inline
+                    #allow_dead_code
+                    #[allow(missing_docs)]
+                    #availability
+                    #( #cfgs )*
+                    #( #deprecated )*
+                    #accessor_vis fn #setter_name (&mut self, value: #ty) {
+                        match *self {
+                            #( #setter_arms )*
+                        }
+                    }
+
+                    #replace
+                }
+            };
+            let pin_mut = if field.pin {
+                let pin_name = Ident::new(
+                    &format!("{}_pin_mut", unraw(accessor_name)),
+                    accessor_name.span(),
+                );
+                let pin_ty = optional_type(field, quote!(::core::pin::Pin<&mut #ty>));
+                let pin_arms = variants.iter().enumerate().map(|(idx, variant)| {
+                    let ident = &variant.ident;
+                    let variant_cfgs = variant_cfgs(variant);
+                    missing(idx, variant).unwrap_or_else(|| {
+                        let pinned = quote!(::core::pin::Pin::new_unchecked(#field_name));
+                        let val = optional_value(field, Some(pinned));
+                        let pat = args.field_pattern(idx, field, quote!(ref mut));
+                        quote! {
+                            #( #variant_cfgs )*
+                            #enum_name::#ident{#pat, ..} => #val,
+                        }
+                    })
+                });
+                quote! {
+                    #inline
+                    #allow_dead_code
+                    #[allow(missing_docs)]
+                    #availability
+                    #( #cfgs )*
+                    #( #deprecated )*
+                    #accessor_vis fn #pin_name (self: ::core::pin::Pin<&mut Self>) -> #pin_ty {
+                        // Safety: The field is structurally pinned. The pinned enum value is never moved
+                        // here and the field is only accessible via the pinned reference.
+                        unsafe {
+                            match *::core::pin::Pin::get_unchecked_mut(self) {
+                                #( #pin_arms )*
+                            }
+                        }
+                    }
+                }
+            } else {
+                TokenStream2::new()
+            };
+            let into = if args.into {
+                let into_name = Ident::new(
+                    &format!("into_{}", unraw(accessor_name)),
+                    accessor_name.span(),
+                );
+                let into_ty = optional_type(field, quote!(#ty));
+                let into_arms = variants.iter().enumerate().map(|(idx, variant)| {
+                    let ident = &variant.ident;
+                    let variant_cfgs = variant_cfgs(variant);
+                    missing(idx, variant).unwrap_or_else(|| {
+                        let val = optional_value(field, Some(quote!(#field_name)));
+                        let pat = args.field_pattern(idx, field, TokenStream2::new());
+                        quote! {
+                            #( #variant_cfgs )*
+                            #enum_name::#ident{#pat, ..} => #val,
+                        }
+                    })
+                });
+                quote! {
+                    #inline
+                    #allow_dead_code
+                    #[allow(missing_docs)]
+                    #availability
+                    #( #cfgs )*
+                    #( #deprecated )*
+                    #accessor_vis fn #into_name (self) -> #into_ty {
+                        match self {
+                            #( #into_arms )*
+                        }
+                    }
+                }
+            } else {
+                TokenStream2::new()
+            };
+            let getter = if args.getter {
+                quote! {
+                    #inline
+                    #allow_dead_code
+                    #[allow(missing_docs)]
+                    #must_use
+                    #getter_docs
+                    #( #cfgs )*
+                    #( #deprecated )*
+                    #accessor_vis #getter_constness fn #accessor_name (&self) -> #ret_ty {
+                        // Matching `*self` instead of `self` so that the match without arms is valid
+                        // for an enum without variants
+                        match *self {
+                            #( #arms )*
+                        }
+                    }
+                }
+            } else {
+                TokenStream2::new()
+            };
+            let mut_getter = if args.mut_getter {
+                quote! {
+                    #inline
+                    #allow_dead_code
+                    #[allow(missing_docs)]
+                    #availability
+                    #( #cfgs )*
+                    #( #deprecated )*
+                    #accessor_vis fn #mut_name (&mut self) -> #mut_ty {
+                        match *self {
+                            #( #mut_arms )*
+                        }
+                    }
+                }
+            } else {
+                TokenStream2::new()
+            };
+            let methods = quote! {
+                #getter
+                #mut_getter
+                #setter
+                #pin_mut
+                #into
+            };
+            respan_method(methods, field_name.span())
+        })
+        .collect::<Vec<_>>();
+
+    if args.common_refs {
+        // Elements of tuple type cannot be removed by #[cfg]
+        if let Some(field) = args.fields.iter().find(|f| f.cfgs().next().is_some()) {
+            return Err(Error::new_spanned(
+                field.name(),
+                format!(
+                    "Common field '{}' with #[cfg] cannot be used with 'common_refs' option",
+                    field.name()
+                ),
+            ));
+        }
+        let tys = args.fields.iter().map(|field| {
+            let ty = &field.field.ty;
+            optional_type(field, quote!(&#ty))
+        });
+        let arms = variants.iter().enumerate().map(|(idx, variant)| {
+            let ident = &variant.ident;
+            let pats = args
+                .fields
+                .iter()
+                .filter(|f| args.has_field(idx, f))
+                .map(|f| args.field_pattern(idx, f, quote!(ref)));
+            let values = args.fields.iter().map(|f| {
+                let name = f.name();
+                let value = if args.has_field(idx, f) {
+                    Some(quote!(#name))
+                } else {
+                    None
+                };
+                optional_value(f, value)
+            });
+            let variant_cfgs = variant_cfgs(variant);
+            quote! {
+                #( #variant_cfgs )*
+                #enum_name::#ident{ #( #pats, )* .. } => ( #( #values, )* ),
+            }
+        });
+        let method = quote! {
+            #inline
+            #allow_dead_code
+            #[allow(missing_docs)]
+            #must_use
+            #accessor_vis #constness fn common_refs(&self) -> ( #( #tys, )* ) {
+                match *self {
+                    #( #arms )*
+                }
+            }
+        };
+        accessors.push(respan_method(method, enum_name.span()));
+    }
+
+    let predicates = variants.iter().map(|variant| {
+        let pred_name = Ident::new(
+            &format!("is_{}", variant_method_name(args, &variant.ident)),
+            variant.ident.span(),
+        );
+        let arms = variants.iter().map(|other| {
+            let ident = &other.ident;
+            let variant_cfgs = variant_cfgs(other);
+            let matched = ident == &variant.ident;
+            quote! {
+                #( #variant_cfgs )*
+                #enum_name::#ident{..} => #matched,
+            }
+        });
+        let variant_cfgs = variant_cfgs(variant);
+        let method = quote! {
+            #inline
+            #allow_dead_code
+            #[allow(missing_docs)]
+            #must_use
+            #( #variant_cfgs )*
+            #vis #constness fn #pred_name (&self) -> ::core::primitive::bool {
+                match *self {
+                    #( #arms )*
+                }
+            }
+        };
+        respan_method(method, variant.ident.span())
+    });
+
+    // Names are listed in the declaration order. Names of fields removed by #[cfg] are also removed
+    let field_names = args.fields.iter().map(|field| {
+        let cfgs = field.cfgs();
+        let name = unraw(field.name());
+        quote!( #( #cfgs )* #name, )
+    });
+    let name_arms = variants.iter().map(|variant| {
+        let ident = &variant.ident;
+        let variant_cfgs = variant_cfgs(variant);
+        let name = unraw(ident);
+        quote! {
+            #( #variant_cfgs )*
+            #enum_name::#ident{..} => #name,
+        }
+    });
+    let methods = quote! {
+        #inline
+        #allow_dead_code
+        #[allow(missing_docs)]
+        #must_use
+        #vis #constness fn variant_name(&self) -> &'static ::core::primitive::str {
+            match *self {
+                #( #name_arms )*
+            }
+        }
+
+        #allow_dead_code
+        #[allow(missing_docs)]
+        #vis #constness fn common_field_names() -> &'static [&'static ::core::primitive::str] {
+            &[ #( #field_names )* ]
+        }
+    };
+    let predicates = predicates.chain(std::iter::once(respan_method(methods, enum_name.span())));
+
+    // Hidden methods used by `common_match!` to bind common fields. They match all variants by patterns
+    // so that they don't depend on names and kinds of accessors, and they are always inherent methods
+    let match_helpers = args.fields.iter().map(|field| {
+        let field_name = field.name();
+        let method_name = common_match_method_name(field_name);
+        let ty = &field.field.ty;
+        let ret_ty = optional_type(field, quote!(&#ty));
+        let cfgs = field.cfgs();
+        let arms = variants.iter().enumerate().map(|(idx, variant)| {
+            let ident = &variant.ident;
+            let variant_cfgs = variant_cfgs(variant);
+            if args.has_field(idx, field) {
+                let val = optional_value(field, Some(quote!(#field_name)));
+                let pat = args.field_pattern(idx, field, quote!(ref));
+                quote! {
+                    #( #variant_cfgs )*
+                    #enum_name::#ident{#pat, ..} => #val,
+                }
+            } else {
+                let none = optional_value(field, None);
+                quote! {
+                    #( #variant_cfgs )*
+                    #enum_name::#ident{..} => #none,
+                }
+            }
+        });
+        quote! {
+            #[inline]
+            #[doc(hidden)]
+            #[allow(dead_code)]
+            #( #cfgs )*
+            #vis fn #method_name(&self) -> #ret_ty {
+                match *self {
+                    #( #arms )*
+                }
+            }
+        }
+    });
+    let predicates = predicates.chain(match_helpers);
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let trait_name = match (&args.trait_name, &args.impl_trait) {
+        (Some(name), _) => name,
+        (None, Some(path)) => {
+            let predicates = quote_spanned! {impl_span=>
+                #[allow(deprecated)]
+                #derived
+                impl #impl_generics #enum_name #ty_generics #where_clause {
+                    #( #predicates )*
+                }
+            };
+            return Ok(quote! {
+                #[allow(deprecated)]
+                #[automatically_derived]
+                impl #impl_generics #path for #enum_name #ty_generics #where_clause {
+                    #( #accessors )*
+                }
+
+                #predicates
+            });
+        }
+        (None, None) => {
+            // Generated implementations refer to all variants and their fields. Variants marked with
+            // #[deprecated] should not cause warnings at the enum definition
+            return Ok(quote_spanned! {impl_span=>
+                #[allow(deprecated)]
+                #derived
+                impl #impl_generics #enum_name #ty_generics #where_clause {
+                    #( #accessors )*
+                    #( #predicates )*
+                }
+            });
+        }
+    };
+
+    let mut accessors = parse_common_methods(quote!( #( #accessors )* ))?;
+    let signatures = accessors.iter().map(|method| {
+        let sig = &method.sig;
+        let attrs = method
+            .attrs
+            .iter()
+            .filter(|attr| !attr.path.is_ident("inline"));
+        quote! {
+            #( #attrs )*
+            #sig;
+        }
+    });
+    let signatures = signatures.collect::<Vec<_>>();
+    // #[deprecated] is only put to the methods of the trait since it is useless at the implementation
+    for method in accessors.iter_mut() {
+        method
+            .attrs
+            .retain(|attr| !attr.path.is_ident("deprecated"));
+    }
+    let trait_generics = common_generics(&args.fields, &input.generics);
+    let (_, trait_ty_generics, trait_where_clause) = trait_generics.split_for_impl();
+    let predicates = quote_spanned! {impl_span=>
+        #[allow(deprecated)]
+        #derived
+        impl #impl_generics #enum_name #ty_generics #where_clause {
+            #( #predicates )*
+        }
+    };
+    Ok(quote! {
+        #vis trait #trait_name #trait_generics #trait_where_clause {
+            #( #signatures )*
+        }
+
+        #[allow(deprecated)]
+        #[automatically_derived]
+        impl #impl_generics #trait_name #trait_ty_generics for #enum_name #ty_generics #where_clause {
+            #( #accessors )*
+        }
+
+        #predicates
+    })
+}
+
+// Reports a method name defined by an accessor of a common field and by some other generated method
+// at once. Otherwise the collision causes a confusing compile error in the generated impl block.
+fn check_method_names(args: &Args, variants: &Punctuated<Variant, Token![,]>) -> Result<()> {
+    // Method name -> (what defines the method, whether it is defined by an option)
+    let mut defined = HashMap::new();
+    defined.insert(
+        "common_field_names".to_string(),
+        ("'common_field_names()' function".to_string(), false),
+    );
+    defined.insert(
+        "variant_name".to_string(),
+        ("'variant_name()' method".to_string(), false),
+    );
+    if args.kind.is_some() {
+        defined.insert("kind".to_string(), ("'kind' option".to_string(), true));
+    }
+    if args.common_refs {
+        defined.insert(
+            "common_refs".to_string(),
+            ("'common_refs' option".to_string(), true),
+        );
+    }
+    if args.common_struct {
+        defined.insert(
+            "into_common".to_string(),
+            ("'common_struct' option".to_string(), true),
+        );
+        defined.insert(
+            "common".to_string(),
+            ("'common_struct' option".to_string(), true),
+        );
+    }
+    for (idx, variant) in variants.iter().enumerate() {
+        let snake = variant_method_name(args, &variant.ident);
+        // Different variant names can be the same in method names such as `HttpServer` and `HTTPServer`.
+        // Then all methods named after the variants conflict so checking predicates is enough
+        let predicate = format!("is_{}", snake);
+        if let Some((origin, _)) = defined.get(&predicate) {
+            return Err(Error::new_spanned(
+                &variant.ident,
+                format!(
+                    "Method '{}' generated for enum variant {} conflicts with the method generated by {}. Please rename the variant or change case option",
+                    predicate, variant.ident, origin,
+                ),
+            ));
+        }
+        if args.common_struct && can_construct_with_common(args, idx, variant) {
+            defined.insert(
+                format!("{}_with_common", snake),
+                ("'common_struct' option".to_string(), true),
+            );
+        }
+        // Views are defined only for variants which have variant-specific fields
+        let has_own_fields = variant.fields.iter().next().is_some();
+        let is_tuple = match variant.fields {
+            Fields::Unnamed(_) => true,
+            _ => args.variants[idx].tuple_offset.is_some(),
+        };
+        if args.builder && !is_tuple {
+            defined.insert(
+                format!("{}_builder", snake),
+                ("'builder' option".to_string(), true),
+            );
+        }
+        if args.transition && can_transition_to(args, idx, variant) {
+            defined.insert(
+                format!("with_{}", snake),
+                ("'transition' option".to_string(), true),
+            );
+        }
+        if args.constructor {
+            defined.insert(
+                format!("new_{}", snake),
+                ("'constructor' option".to_string(), true),
+            );
+        }
+        if args.view && has_own_fields {
+            defined.insert(format!("as_{}", snake), ("'view' option".to_string(), true));
+        }
+        defined.insert(
+            format!("is_{}", snake),
+            (format!("predicate of variant '{}'", variant.ident), false),
+        );
+    }
+
+    for field in args.fields.iter() {
+        let accessor = unraw(field.accessor_name());
+        let mut names = vec![];
+        if args.getter {
+            names.push(accessor.clone());
+        }
+        if args.mut_getter {
+            names.push(format!("{}_mut", accessor));
+        }
+        if args.setter && !field.variant_optional {
+            names.push(format!("set_{}", accessor));
+            if args.trait_name.is_none() && args.impl_trait.is_none() {
+                names.push(format!("replace_{}", accessor));
+            }
+        }
+        if field.pin {
+            names.push(format!("{}_pin_mut", accessor));
+        }
+        if args.into {
+            names.push(format!("into_{}", accessor));
+        }
+        if args.map {
+            names.push(format!("map_{}", accessor));
+        }
+        for name in names {
+            if let Some((origin, is_option)) = defined.get(&name) {
+                return Err(Error::new_spanned(
+                    field.name(),
+                    format!(
+                        "Method '{}' generated for common field '{}' conflicts with the method generated by {}. Please rename the field or its accessor with #[accessor(name = \"...\")]{}",
+                        name,
+                        field.name(),
+                        origin,
+                        if *is_option { ", or disable the option" } else { "" },
+                    ),
+                ));
+            }
+            defined.insert(name, (format!("common field '{}'", field.name()), false));
+        }
+    }
+    Ok(())
+}
+
+// Name of the identifier without `r#` prefix of raw identifier. Names derived from a raw identifier
+// such as `type_mut` for `r#type` are not keywords.
+fn unraw(ident: &Ident) -> String {
+    let name = ident.to_string();
+    match name.get(..2) {
+        Some("r#") => name[2..].to_string(),
+        _ => name,
+    }
+}
+
+// Name of the variant used in names of methods such as `is_git_hub()` for `GitHub` variant. All methods
+// named after variants use this so that their naming is consistent
+fn variant_method_name(args: &Args, variant: &Ident) -> String {
+    let name = unraw(variant);
+    match args.case {
+        NameCase::Snake => to_snake_case(&name),
+        NameCase::Lower => name.to_lowercase(),
+    }
+}
+
+// Converts PascalCase identifier into snake_case. Consecutive upper case letters are treated as one
+// word (acronym) such as 'HTTPServer' -> 'http_server'.
+fn to_snake_case(ident: &str) -> String {
+    let chars: Vec<char> = ident.chars().collect();
+    let mut snake = String::with_capacity(ident.len() + 4);
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).map_or(false, |n| n.is_lowercase());
+            if prev != '_'
+                && (prev.is_lowercase()
+                    || prev.is_numeric()
+                    || (prev.is_uppercase() && next_is_lower))
+            {
+                snake.push('_');
+            }
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+fn common_struct_name(enum_name: &Ident) -> Ident {
+    Ident::new(&format!("{}Common", enum_name), enum_name.span())
+}
+
+fn common_ref_struct_name(enum_name: &Ident) -> Ident {
+    Ident::new(&format!("{}CommonRef", enum_name), enum_name.span())
+}
+
+// Concrete type of the enum to replace `Self`
+fn self_type(input: &DeriveInput) -> TokenStream2 {
+    let name = &input.ident;
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+    quote!(#name #ty_generics)
+}
+
+fn replace_self_tokens(tokens: TokenStream2, self_ty: &TokenStream2) -> TokenStream2 {
+    tokens
+        .into_iter()
+        .map(|token| match token {
+            TokenTree::Ident(ref i) if i == "Self" => self_ty.clone(),
+            TokenTree::Group(g) => {
+                let mut group = Group::new(g.delimiter(), replace_self_tokens(g.stream(), self_ty));
+                group.set_span(g.span());
+                quote!(#group)
+            }
+            token => quote!(#token),
+        })
+        .collect()
+}
+
+// Sets the span to all tokens including tokens in groups
+fn respan(tokens: TokenStream2, span: Span) -> TokenStream2 {
+    tokens
+        .into_iter()
+        .map(|token| match token {
+            TokenTree::Group(g) => {
+                let mut group = Group::new(g.delimiter(), respan(g.stream(), span));
+                group.set_span(span);
+                TokenTree::Group(group)
+            }
+            mut token => {
+                token.set_span(span);
+                token
+            }
+        })
+        .collect()
+}
+
+// Replaces `Self` in the type with the concrete type of the enum
+fn replace_self(ty: &Type, self_ty: &TokenStream2) -> Type {
+    let replaced = replace_self_tokens(quote!(#ty), self_ty);
+    syn::parse2(replaced).expect("type is still valid after replacing Self")
+}
+
+fn collect_idents(tokens: TokenStream2, idents: &mut HashSet<String>) {
+    for token in tokens {
+        match token {
+            TokenTree::Ident(i) => {
+                idents.insert(i.to_string());
+            }
+            TokenTree::Group(g) => collect_idents(g.stream(), idents),
+            _ => {}
+        }
+    }
+}
+
+// Generic parameters of the enum which are actually used by common fields. Unused parameters cannot
+// be put to a struct.
+fn common_generics(shared: &[CommonField], generics: &Generics) -> Generics {
+    used_generics(shared.iter().map(|field| &field.field.ty), generics)
+}
+
+// Generic arguments to refer to the type with the generic parameters such as `T` and `'a` of `<'a, T: Clone>`
+fn generic_args(generics: &Generics) -> Vec<TokenStream2> {
+    generics
+        .params
+        .iter()
+        .map(|param| match *param {
+            GenericParam::Type(ref t) => {
+                let ident = &t.ident;
+                quote!(#ident)
+            }
+            GenericParam::Lifetime(ref l) => {
+                let lifetime = &l.lifetime;
+                quote!(#lifetime)
+            }
+            GenericParam::Const(ref c) => {
+                let ident = &c.ident;
+                quote!(#ident)
+            }
+        })
+        .collect()
+}
+
+// Generic parameters of the enum which are used by the given types
+fn used_generics<'a>(types: impl Iterator<Item = &'a Type>, generics: &Generics) -> Generics {
+    let mut used = HashSet::new();
+    for ty in types {
+        collect_idents(quote!(#ty), &mut used);
+    }
+
+    let param_name = |param: &GenericParam| match *param {
+        GenericParam::Type(ref t) => t.ident.to_string(),
+        GenericParam::Lifetime(ref l) => l.lifetime.ident.to_string(),
+        GenericParam::Const(ref c) => c.ident.to_string(),
+    };
+    let unused: HashSet<String> = generics
+        .params
+        .iter()
+        .map(param_name)
+        .filter(|name| !used.contains(name))
+        .collect();
+
+    let mut filtered = generics.clone();
+    filtered.params = generics
+        .params
+        .iter()
+        .filter(|param| !unused.contains(&param_name(param)))
+        .cloned()
+        .collect();
+    if let Some(ref mut where_clause) = filtered.where_clause {
+        where_clause.predicates = where_clause
+            .predicates
+            .iter()
+            .filter(|pred| {
+                let mut idents = HashSet::new();
+                collect_idents(quote!(#pred), &mut idents);
+                idents.is_disjoint(&unused)
+            })
+            .cloned()
+            .collect();
+    }
+    if filtered.params.is_empty() {
+        filtered.lt_token = None;
+        filtered.gt_token = None;
+    }
+    filtered
+}
+
+fn generate_common_struct(args: &Args, input: &DeriveInput) -> Result<TokenStream2> {
+    let enum_name = &input.ident;
+    let vis = args.vis.as_ref().unwrap_or(&input.vis);
+    let variants = enum_variants(input)?;
+
+    let struct_name = common_struct_name(enum_name);
+    let generics = common_generics(&args.fields, &input.generics);
+    let (_, struct_ty_generics, struct_where_clause) = generics.split_for_impl();
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = args.fields.iter().map(|field| {
+        let mut f = field.field.clone();
+        if let Visibility::Inherited = f.vis {
+            f.vis = input.vis.clone();
+        }
+        let ty = &f.ty;
+        f.ty = syn::parse2(optional_type(field, quote!(#ty))).unwrap();
+        f
+    });
+    let arms = variants.iter().enumerate().map(|(idx, variant)| {
+        let ident = &variant.ident;
+        let bound = args
+            .fields
+            .iter()
+            .filter(|field| args.has_field(idx, field))
+            .map(|field| {
+                let cfgs = field.cfgs();
+                let pat = args.field_pattern(idx, field, TokenStream2::new());
+                quote!(#( #cfgs )* #pat)
+            });
+        let inits = args.fields.iter().map(|field| {
+            let name = field.name();
+            let cfgs = field.cfgs();
+            let value = if args.has_field(idx, field) {
+                optional_value(field, Some(quote!(#name)))
+            } else {
+                optional_value(field, None)
+            };
+            quote!(#( #cfgs )* #name: #value)
+        });
+        let variant_cfgs = variant_cfgs(variant);
+        quote! {
+            #( #variant_cfgs )*
+            #enum_name::#ident{ #( #bound, )* .. } => #struct_name { #( #inits, )* },
+        }
+    });
+    let struct_vis = &input.vis;
+
+    // `ECommonRef` borrows the common fields of the enum value. Lifetime of the struct is elided in the
+    // return type of `common()`
+    let ref_name = common_ref_struct_name(enum_name);
+    let ref_args = generic_args(&generics);
+    let ref_ret = quote!(#ref_name<'_ #( , #ref_args )*>);
+    let mut ref_generics = generics.clone();
+    ref_generics.params.insert(0, syn::parse_quote!('__common));
+    ref_generics.lt_token = Some(Default::default());
+    ref_generics.gt_token = Some(Default::default());
+    let ref_fields = args.fields.iter().map(|field| {
+        let mut f = field.field.clone();
+        if let Visibility::Inherited = f.vis {
+            f.vis = input.vis.clone();
+        }
+        let ty = &f.ty;
+        f.ty = syn::parse2(optional_type(field, quote!(&'__common #ty))).unwrap();
+        f
+    });
+    let ref_arms = variants.iter().enumerate().map(|(idx, variant)| {
+        let ident = &variant.ident;
+        let bound = args
+            .fields
+            .iter()
+            .filter(|field| args.has_field(idx, field))
+            .map(|field| {
+                let cfgs = field.cfgs();
+                let pat = args.field_pattern(idx, field, quote!(ref));
+                quote!(#( #cfgs )* #pat)
+            });
+        let inits = args.fields.iter().map(|field| {
+            let name = field.name();
+            let cfgs = field.cfgs();
+            let value = if args.has_field(idx, field) {
+                optional_value(field, Some(quote!(#name)))
+            } else {
+                optional_value(field, None)
+            };
+            quote!(#( #cfgs )* #name: #value)
+        });
+        let variant_cfgs = variant_cfgs(variant);
+        quote! {
+            #( #variant_cfgs )*
+            #enum_name::#ident{ #( #bound, )* .. } => #ref_name { #( #inits, )* },
+        }
+    });
+
+    // `xxx_with_common()` constructs variant `xxx` from the common struct and variant-specific fields
+    let mut constructors = vec![];
+    for (idx, variant) in variants.iter().enumerate() {
+        if !can_construct_with_common(args, idx, variant) {
+            continue;
+        }
+        let variant_name = &variant.ident;
+        let method_name = Ident::new(
+            &format!("{}_with_common", variant_method_name(args, variant_name)),
+            Span::call_site(),
+        );
+        let own_fields = variant_specific_fields(args, variant);
+        let params = own_fields.iter().map(|field| {
+            let name = &field.ident;
+            let ty = &field.ty;
+            let cfgs = field.attrs.iter().filter(|attr| attr.path.is_ident("cfg"));
+            quote!(#( #cfgs )* #name: #ty)
+        });
+        let carried = args
+            .fields
+            .iter()
+            .filter(|f| args.has_field(idx, f))
+            .map(|field| {
+                let name = field.name();
+                let cfgs = field.cfgs();
+                quote!(#( #cfgs )* #name)
+            })
+            .collect::<Vec<_>>();
+        let carried = &carried;
+        let inits = own_fields.iter().map(|field| {
+            let name = &field.ident;
+            let cfgs = field.attrs.iter().filter(|attr| attr.path.is_ident("cfg"));
+            quote!(#( #cfgs )* #name)
+        });
+        let variant_cfgs = variant_cfgs(variant);
+        constructors.push(quote! {
+            #[inline]
+            #[allow(dead_code)]
+            #[allow(missing_docs)]
+            #( #variant_cfgs )*
+            #vis fn #method_name(common: #struct_name #struct_ty_generics, #( #params ),*) -> Self {
+                let #struct_name { #( #carried, )* .. } = common;
+                #enum_name::#variant_name{ #( #inits, )* #( #carried, )* }
+            }
+        });
+    }
+
+    Ok(quote! {
+        #struct_vis struct #struct_name #generics #struct_where_clause {
+            #( #fields, )*
+        }
+
+        #struct_vis struct #ref_name #ref_generics #struct_where_clause {
+            #( #ref_fields, )*
+        }
+
+        #[allow(deprecated)]
+        #[automatically_derived]
+        impl #impl_generics #enum_name #ty_generics #where_clause {
+            #[inline]
+            #[allow(dead_code)]
+            #[allow(missing_docs)]
+            #vis fn into_common(self) -> #struct_name #struct_ty_generics {
+                match self {
+                    #( #arms )*
+                }
+            }
+
+            #[inline]
+            #[allow(dead_code)]
+            #[allow(missing_docs)]
+            #vis fn common(&self) -> #ref_ret {
+                match *self {
+                    #( #ref_arms )*
+                }
+            }
+
+            #( #constructors )*
+        }
+
+        #[allow(deprecated)]
+        #[automatically_derived]
+        impl #impl_generics ::core::convert::From<#enum_name #ty_generics> for #struct_name #struct_ty_generics #where_clause {
+            #[inline]
+            fn from(e: #enum_name #ty_generics) -> Self {
+                e.into_common()
+            }
+        }
+    })
+}
+
+fn kind_enum_name(enum_name: &Ident) -> Ident {
+    Ident::new(&format!("{}Kind", enum_name), enum_name.span())
+}
+
+fn generate_kind_enum(
+    args: &Args,
+    opts: &KindOptions,
+    input: &DeriveInput,
+) -> Result<TokenStream2> {
+    let enum_name = &input.ident;
+    let vis = args.vis.as_ref().unwrap_or(&input.vis);
+    let variants = enum_variants(input)?;
+
+    let kind_name = kind_enum_name(enum_name);
+    let kind_variants = variants.iter().map(|variant| {
+        let ident = &variant.ident;
+        let variant_cfgs = variant_cfgs(variant);
+        quote!(#( #variant_cfgs )* #ident)
+    });
+    let arms = variants.iter().map(|variant| {
+        let ident = &variant.ident;
+        let variant_cfgs = variant_cfgs(variant);
+        quote! {
+            #( #variant_cfgs )*
+            #enum_name::#ident{..} => #kind_name::#ident,
+        }
+    });
+    let kind_vis = opts.vis.as_ref().unwrap_or(&input.vis);
+    let derives = &opts.derives;
+    let repr = opts.repr.as_ref().map(|repr| quote!(#[repr(#repr)]));
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq #( , #derives )*)]
+        #repr
+        #kind_vis enum #kind_name {
+            #( #kind_variants, )*
+        }
+
+        #[allow(deprecated)]
+        #[automatically_derived]
+        impl #impl_generics #enum_name #ty_generics #where_clause {
+            #[inline]
+            #[allow(dead_code)]
+            #[allow(missing_docs)]
+            #vis fn kind(&self) -> #kind_name {
+                match *self {
+                    #( #arms )*
+                }
+            }
+        }
+    })
+}
+
+// Generates AsRef implementations for common fields with #[as_ref] attribute
+fn generate_as_ref(args: &Args, input: &DeriveInput) -> Result<TokenStream2> {
+    let enum_name = &input.ident;
+    let variants = enum_variants(input)?;
+    let mut targets = HashSet::new();
+    let mut impls = vec![];
+
+    for field in args.fields.iter() {
+        for target in field.as_ref.iter() {
+            let ty = &field.field.ty;
+            let span_tokens = match target {
+                Some(target) => quote!(#target),
+                None => {
+                    let name = field.name();
+                    quote!(#name)
+                }
+            };
+            let target_ty = match target {
+                Some(target) => quote!(#target),
+                None => quote!(#ty),
+            };
+            if field.variant_optional {
+                return Err(Error::new_spanned(
+                    span_tokens,
+                    format!(
+                        "#[as_ref] cannot be put to common field '{}' marked with #[variant_optional]",
+                        field.name()
+                    ),
+                ));
+            }
+            if !targets.insert(target_ty.to_string()) {
+                return Err(Error::new_spanned(
+                    span_tokens,
+                    format!(
+                        "#[as_ref] for type '{}' is put to more than one common field",
+                        target_ty
+                    ),
+                ));
+            }
+
+            let mut generics = input.generics.clone();
+            let name = field.name();
+            let value = match target {
+                Some(target) => {
+                    generics
+                        .make_where_clause()
+                        .predicates
+                        .push(syn::parse_quote!(#ty: ::core::convert::AsRef<#target>));
+                    quote!(::core::convert::AsRef::<#target>::as_ref(#name))
+                }
+                None => quote!(#name),
+            };
+            let arms = variants.iter().enumerate().map(|(idx, variant)| {
+                let ident = &variant.ident;
+                let variant_cfgs = variant_cfgs(variant);
+                let pat = args.field_pattern(idx, field, quote!(ref));
+                quote! {
+                    #( #variant_cfgs )*
+                    #enum_name::#ident{#pat, ..} => #value,
+                }
+            });
+            let cfgs = field.cfgs();
+            let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+            impls.push(quote! {
+                #( #cfgs )*
+                #[allow(deprecated)]
+                #[automatically_derived]
+                impl #impl_generics ::core::convert::AsRef<#target_ty> for #enum_name #ty_generics #where_clause {
+                    #[inline]
+                    fn as_ref(&self) -> &#target_ty {
+                        match *self {
+                            #( #arms )*
+                        }
+                    }
+                }
+            });
+        }
+    }
+
+    Ok(quote!( #( #impls )* ))
+}
+
+// Generates Default implementation which constructs the variant with default values of all its fields.
+// This takes the enum after injecting common fields.
+fn generate_default(
+    args: &Args,
+    variant_name: &Ident,
+    input: &DeriveInput,
+) -> Result<TokenStream2> {
+    let enum_name = &input.ident;
+    let variants = enum_variants(input)?;
+    let variant = match variants.iter().find(|v| v.ident == *variant_name) {
+        Some(v) => v,
+        None => {
+            return Err(Error::new_spanned(
+                variant_name,
+                format!(
+                    "Variant '{}' for default option does not exist in enum {}",
+                    variant_name, enum_name
+                ),
+            ))
+        }
+    };
+
+    // Default value of common field given by `x: T = expr`. Fields of tuple variants are not matched
+    // since they don't have names
+    let default_value = |field: &Field| {
+        args.fields
+            .iter()
+            .find(|f| field.ident.as_ref() == Some(f.name()))
+            .and_then(|f| f.default.as_ref())
+    };
+
+    let mut generics = input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for field in variant.fields.iter() {
+        if default_value(field).is_some() {
+            continue;
+        }
+        let ty = &field.ty;
+        let mut idents = HashSet::new();
+        collect_idents(quote!(#ty), &mut idents);
+        if !idents.contains(&enum_name.to_string()) {
+            where_clause
+                .predicates
+                .push(syn::parse_quote!(#ty: ::core::default::Default));
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let inits = variant.fields.iter().map(|field| {
+        let cfgs = field.attrs.iter().filter(|attr| attr.path.is_ident("cfg"));
+        let name = &field.ident;
+        let colon = &field.colon_token;
+        match default_value(field) {
+            Some(expr) => quote!(#( #cfgs )* #name #colon #expr),
+            None => quote!(#( #cfgs )* #name #colon ::core::default::Default::default()),
+        }
+    });
+    let value = match variant.fields {
+        Fields::Named(_) => quote!(#enum_name::#variant_name { #( #inits, )* }),
+        Fields::Unnamed(_) => quote!(#enum_name::#variant_name( #( #inits, )* )),
+        Fields::Unit => quote!(#enum_name::#variant_name),
+    };
+
+    Ok(quote! {
+        #[allow(deprecated)]
+        #[automatically_derived]
+        impl #impl_generics ::core::default::Default for #enum_name #ty_generics #where_clause {
+            fn default() -> Self {
+                #value
+            }
+        }
+    })
+}
+
+fn view_struct_name(enum_name: &Ident, variant_name: &Ident) -> Ident {
+    Ident::new(
+        &format!("{}{}Ref", enum_name, variant_name),
+        Span::call_site(),
+    )
+}
+
+// Generates a struct to borrow variant-specific fields for each variant and `as_xxx()` methods to get
+// it. Variants which have no variant-specific field are skipped.
+fn generate_views(args: &Args, input: &DeriveInput) -> Result<TokenStream2> {
+    let enum_name = &input.ident;
+    let vis = args.vis.as_ref().unwrap_or(&input.vis);
+    let view_vis = &input.vis;
+    let variants = enum_variants(input)?;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut structs = vec![];
+    let mut methods = vec![];
+    for (idx, variant) in variants.iter().enumerate() {
+        if variant.fields.iter().next().is_none() {
+            continue;
+        }
+        // Positional fields of tuple variant are shifted when common fields are prepended
+        let shift = match args.variants[idx].tuple_offset {
+            Some(0) if args.prepend => args
+                .fields
+                .iter()
+                .filter(|f| args.has_field(idx, f))
+                .count(),
+            _ => 0,
+        };
+
+        let variant_name = &variant.ident;
+        let view_name = view_struct_name(enum_name, variant_name);
+        let mut generics = used_generics(variant.fields.iter().map(|f| &f.ty), &input.generics);
+        // Lifetime of the view is elided in the return type of `as_xxx()`
+        let ret_args = generic_args(&generics);
+        let ret = quote!(#view_name<'_ #( , #ret_args )*>);
+        generics.params.insert(0, syn::parse_quote!('__view));
+        generics.lt_token = Some(Default::default());
+        generics.gt_token = Some(Default::default());
+        let (_, _, view_where_clause) = generics.split_for_impl();
+
+        let decls = variant.fields.iter().map(|f| {
+            let name = &f.ident;
+            let colon = &f.colon_token;
+            let ty = &f.ty;
+            quote!(#view_vis #name #colon &'__view #ty)
+        });
+        let (decl, pat, value) = match variant.fields {
+            Fields::Named(ref f) => {
+                let names: Vec<_> = f.named.iter().map(|f| &f.ident).collect();
+                let names = &names;
+                (
+                    quote!(#generics #view_where_clause { #( #decls, )* }),
+                    quote!({ #( ref #names, )* .. }),
+                    quote!(#view_name { #( #names, )* }),
+                )
+            }
+            _ => {
+                let bindings: Vec<_> = (0..variant.fields.iter().count())
+                    .map(|i| Ident::new(&format!("__field{}", i), Span::call_site()))
+                    .collect();
+                let bindings = &bindings;
+                let indices = (0..bindings.len()).map(|i| syn::Index::from(i + shift));
+                (
+                    quote!(#generics ( #( #decls, )* ) #view_where_clause;),
+                    quote!({ #( #indices: ref #bindings, )* .. }),
+                    quote!(#view_name( #( #bindings, )* )),
+                )
+            }
+        };
+        let cfgs = variant_cfgs(variant);
+        let cfgs = &cfgs;
+        structs.push(quote! {
+            #[allow(missing_docs)]
+            #( #cfgs )*
+            #view_vis struct #view_name #decl
+        });
+
+        let method_name = Ident::new(
+            &format!("as_{}", variant_method_name(args, variant_name)),
+            Span::call_site(),
+        );
+        let arms = variants.iter().map(|other| {
+            let ident = &other.ident;
+            if ident == variant_name {
+                quote!(#( #cfgs )* #enum_name::#ident #pat => ::core::option::Option::Some(#value),)
+            } else {
+                let other_cfgs = variant_cfgs(other);
+                quote!(#( #other_cfgs )* #enum_name::#ident{..} => ::core::option::Option::None,)
+            }
+        });
+        methods.push(quote! {
+            #[inline]
+            #[allow(dead_code)]
+            #[allow(missing_docs)]
+            #( #cfgs )*
+            #vis fn #method_name(&self) -> ::core::option::Option<#ret> {
+                match self {
+                    #( #arms )*
+                }
+            }
+        });
+    }
+
+    Ok(quote! {
+        #( #structs )*
+
+        #[allow(deprecated)]
+        #[automatically_derived]
+        impl #impl_generics #enum_name #ty_generics #where_clause {
+            #( #methods )*
+        }
+    })
+}
+
+fn builder_struct_name(enum_name: &Ident, variant_name: &Ident) -> Ident {
+    Ident::new(
+        &format!("{}{}Builder", enum_name, variant_name),
+        Span::call_site(),
+    )
+}
+
+// Returns whether `with_xxx()` method to transition to the variant is defined. Tuple variants are not
+// supported since their fields don't have names. Common fields of the variant must be carried over from
+// all other variants so the variant cannot have optional common fields skipped by other variants.
+fn can_transition_to(args: &Args, idx: usize, variant: &Variant) -> bool {
+    if let Fields::Unnamed(_) = variant.fields {
+        return false;
+    }
+    if args.variants[idx].tuple_offset.is_some() {
+        return false;
+    }
+    args.fields.iter().all(|field| {
+        !args.has_field(idx, field) || (0..args.variants.len()).all(|i| args.has_field(i, field))
+    })
+}
+
+// Returns whether `xxx_with_common()` method to construct the variant from the common struct is defined.
+// Tuple variants are not supported since their fields don't have names. The variant cannot have optional
+// common fields since they may be `None` in the common struct.
+fn can_construct_with_common(args: &Args, idx: usize, variant: &Variant) -> bool {
+    if let Fields::Unnamed(_) = variant.fields {
+        return false;
+    }
+    if args.variants[idx].tuple_offset.is_some() {
+        return false;
+    }
+    args.fields
+        .iter()
+        .all(|field| !field.variant_optional || !args.has_field(idx, field))
+}
+
+// Fields of the variant except for common fields. A field overriding a common field with
+// `allow_override` is treated as the common field.
+fn variant_specific_fields<'a>(args: &Args, variant: &'a Variant) -> Vec<&'a Field> {
+    variant
+        .fields
+        .iter()
+        .filter(|f| {
+            !args
+                .fields
+                .iter()
+                .any(|c| f.ident.as_ref() == Some(c.name()))
+        })
+        .collect()
+}
+
+// Generates `with_xxx()` methods which change the variant of the enum value to `xxx` while keeping
+// values of common fields. Only variant-specific fields are given as arguments.
+fn generate_transitions(args: &Args, input: &DeriveInput) -> Result<TokenStream2> {
+    let enum_name = &input.ident;
+    let vis = args.vis.as_ref().unwrap_or(&input.vis);
+    let variants = enum_variants(input)?;
+    let inline = &args.inline;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut methods = vec![];
+    for (idx, variant) in variants.iter().enumerate() {
+        if !can_transition_to(args, idx, variant) {
+            continue;
+        }
+        let variant_name = &variant.ident;
+        let method_name = Ident::new(
+            &format!("with_{}", variant_method_name(args, variant_name)),
+            Span::call_site(),
+        );
+        // A variant-specific field overriding a common field with `allow_override` is carried over
+        let own_fields = variant_specific_fields(args, variant);
+        let params = own_fields.iter().map(|field| {
+            let name = &field.ident;
+            let ty = &field.ty;
+            let cfgs = field.attrs.iter().filter(|attr| attr.path.is_ident("cfg"));
+            quote!(#( #cfgs )* #name: #ty)
+        });
+        let carried = args
+            .fields
+            .iter()
+            .filter(|f| args.has_field(idx, f))
+            .collect::<Vec<_>>();
+        let inits = own_fields
+            .iter()
+            .map(|field| {
+                let name = &field.ident;
+                let cfgs = field.attrs.iter().filter(|attr| attr.path.is_ident("cfg"));
+                quote!(#( #cfgs )* #name)
+            })
+            .chain(carried.iter().map(|field| {
+                let name = field.name();
+                let cfgs = field.cfgs();
+                quote!(#( #cfgs )* #name)
+            }))
+            .collect::<Vec<_>>();
+        let inits = &inits;
+        let arms = variants.iter().enumerate().map(|(src, other)| {
+            let ident = &other.ident;
+            let other_cfgs = variant_cfgs(other);
+            let pats = carried.iter().map(|field| {
+                let cfgs = field.cfgs();
+                let pat = args.field_pattern(src, field, TokenStream2::new());
+                quote!(#( #cfgs )* #pat)
+            });
+            quote! {
+                #( #other_cfgs )*
+                #enum_name::#ident{#( #pats, )* ..} => #enum_name::#variant_name{ #( #inits, )* },
+            }
+        });
+
+        let cfgs = variant_cfgs(variant);
+        methods.push(quote! {
+            #inline
+            #[allow(dead_code)]
+            #[allow(missing_docs)]
+            #( #cfgs )*
+            #vis fn #method_name(self, #( #params ),*) -> Self {
+                match self {
+                    #( #arms )*
+                }
+            }
+        });
+    }
+
+    Ok(quote! {
+        #[allow(deprecated)]
+        #[automatically_derived]
+        impl #impl_generics #enum_name #ty_generics #where_clause {
+            #( #methods )*
+        }
+    })
+}
+
+// Generates `new_xxx()` constructors which take all fields of variant `xxx` as arguments in the order of
+// the fields. The enum must be the expanded one so that the fields contain common fields.
+fn generate_constructors(args: &Args, input: &DeriveInput) -> Result<TokenStream2> {
+    let enum_name = &input.ident;
+    let vis = args.vis.as_ref().unwrap_or(&input.vis);
+    let variants = enum_variants(input)?;
+    let inline = &args.inline;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let methods = variants.iter().map(|variant| {
+        let ident = &variant.ident;
+        let method_name = Ident::new(
+            &format!("new_{}", variant_method_name(args, ident)),
+            ident.span(),
+        );
+        let (params, inits): (Vec<_>, Vec<_>) = variant
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let cfgs = field.attrs.iter().filter(|attr| attr.path.is_ident("cfg"));
+                let cfgs = quote!( #( #cfgs )* );
+                let ty = &field.ty;
+                match field.ident {
+                    Some(ref name) => (quote!(#cfgs #name: #ty), quote!(#cfgs #name)),
+                    None => {
+                        let name = Ident::new(&format!("field{}", i), Span::call_site());
+                        let index = syn::Index::from(i);
+                        (quote!(#cfgs #name: #ty), quote!(#cfgs #index: #name))
+                    }
+                }
+            })
+            .unzip();
+        let variant_cfgs = variant_cfgs(variant);
+        quote! {
+            #inline
+            #[allow(dead_code)]
+            #[allow(missing_docs)]
+            #( #variant_cfgs )*
+            #vis fn #method_name( #( #params ),* ) -> Self {
+                #enum_name::#ident{ #( #inits, )* }
+            }
+        }
+    });
+
+    Ok(quote! {
+        #[allow(deprecated)]
+        #[automatically_derived]
+        impl #impl_generics #enum_name #ty_generics #where_clause {
+            #( #methods )*
+        }
+    })
+}
+
+// Generates `map_xxx()` methods which transform the value of common field `xxx` by a function. Since the
+// field is moved out of the enum value, all fields of the variant are moved and the variant is rebuilt.
+// The enum must be the expanded one so that the patterns contain common fields.
+fn generate_maps(args: &Args, input: &DeriveInput) -> Result<TokenStream2> {
+    let enum_name = &input.ident;
+    let vis = args.vis.as_ref().unwrap_or(&input.vis);
+    let variants = enum_variants(input)?;
+    let inline = &args.inline;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let methods = args.fields.iter().map(|field| {
+        let accessor_name = field.accessor_name();
+        let method_name = Ident::new(
+            &format!("map_{}", unraw(accessor_name)),
+            accessor_name.span(),
+        );
+        let ty = &field.field.ty;
+        let cfgs = field.cfgs();
+        let arms = variants.iter().enumerate().map(|(idx, variant)| {
+            let ident = &variant.ident;
+            let variant_cfgs = variant_cfgs(variant);
+            if !args.has_field(idx, field) {
+                return quote! {
+                    #( #variant_cfgs )*
+                    __value @ #enum_name::#ident{..} => __value,
+                };
+            }
+            let target = args.member(idx, field).to_string();
+            let (pats, inits): (Vec<_>, Vec<_>) = variant
+                .fields
+                .iter()
+                .enumerate()
+                .map(|(i, f)| {
+                    let cfgs = f.attrs.iter().filter(|attr| attr.path.is_ident("cfg"));
+                    let cfgs = quote!( #( #cfgs )* );
+                    let member = match f.ident {
+                        Some(ref name) => quote!(#name),
+                        None => {
+                            let index = syn::Index::from(i);
+                            quote!(#index)
+                        }
+                    };
+                    let binding = Ident::new(&format!("__field{}", i), Span::call_site());
+                    let value = if member.to_string() == target {
+                        quote!(f(#binding))
+                    } else {
+                        quote!(#binding)
+                    };
+                    (
+                        quote!(#cfgs #member: #binding),
+                        quote!(#cfgs #member: #value),
+                    )
+                })
+                .unzip();
+            quote! {
+                #( #variant_cfgs )*
+                #enum_name::#ident{ #( #pats, )* } => #enum_name::#ident{ #( #inits, )* },
+            }
+        });
+        quote! {
+            #inline
+            #[allow(dead_code)]
+            #[allow(missing_docs)]
+            #( #cfgs )*
+            #vis fn #method_name(self, f: impl ::core::ops::FnOnce(#ty) -> #ty) -> Self {
+                match self {
+                    #( #arms )*
+                }
+            }
+        }
+    });
+
+    Ok(quote! {
+        #[allow(deprecated)]
+        #[automatically_derived]
+        impl #impl_generics #enum_name #ty_generics #where_clause {
+            #( #methods )*
+        }
+    })
+}
+
+// Generates a builder struct for each variant. All fields of the variant including common fields must
+// be set before building the enum value, otherwise `build()` returns an error.
+fn generate_builders(args: &Args, input: &DeriveInput) -> Result<TokenStream2> {
+    let enum_name = &input.ident;
+    let vis = args.vis.as_ref().unwrap_or(&input.vis);
+    let builder_vis = &input.vis;
+    let variants = enum_variants(input)?;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let generics = &input.generics;
+
+    let mut builders = vec![];
+    let mut constructors = vec![];
+    for (idx, variant) in variants.iter().enumerate() {
+        let own_fields: Vec<&Field> = match variant.fields {
+            Fields::Named(ref f) => f.named.iter().collect(),
+            Fields::Unit => vec![],
+            // Error is reported while injecting common fields
+            Fields::Unnamed(_) => continue,
+        };
+        // Builder is not supported for tuple variants since their fields don't have names
+        if args.variants[idx].tuple_offset.is_some() {
+            continue;
+        }
+        let mut fields = own_fields.clone();
+        for shared in args.fields.iter() {
+            let name = shared.name();
+            if args.has_field(idx, shared)
+                && !own_fields.iter().any(|f| f.ident.as_ref() == Some(name))
+            {
+                fields.push(&shared.field);
+            }
+        }
+
+        let variant_name = &variant.ident;
+        let builder_name = builder_struct_name(enum_name, variant_name);
+        let cfgs = |field: &Field| {
+            field
+                .attrs
+                .iter()
+                .filter(|attr| attr.path.is_ident("cfg"))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        let self_ty = self_type(input);
+        let decls = fields.iter().map(|field| {
+            let name = &field.ident;
+            let ty = replace_self(&field.ty, &self_ty);
+            let cfgs = cfgs(field);
+            quote!(#( #cfgs )* #name: ::core::option::Option<#ty>)
+        });
+        let inits = fields.iter().map(|field| {
+            let name = &field.ident;
+            let cfgs = cfgs(field);
+            quote!(#( #cfgs )* #name: ::core::option::Option::None)
+        });
+        let setters = fields.iter().map(|field| {
+            let name = &field.ident;
+            let ty = replace_self(&field.ty, &self_ty);
+            let cfgs = cfgs(field);
+            quote! {
+                #[inline]
+                #[allow(dead_code)]
+                #[allow(missing_docs)]
+                #( #cfgs )*
+                #vis fn #name(mut self, value: #ty) -> Self {
+                    self.#name = ::core::option::Option::Some(value);
+                    self
+                }
+            }
+        });
+        let builds = fields.iter().map(|field| {
+            let name = &field.ident;
+            let cfgs = cfgs(field);
+            let msg = format!(
+                "Field '{}' is not set to builder of {}::{}",
+                name.as_ref().unwrap(),
+                enum_name,
+                variant_name
+            );
+            let default = args
+                .fields
+                .iter()
+                .find(|f| field.ident.as_ref() == Some(f.name()))
+                .and_then(|f| f.default.as_ref());
+            match default {
+                Some(expr) => quote!(#( #cfgs )* #name: self.#name.unwrap_or_else(|| #expr)),
+                None => quote!(#( #cfgs )* #name: self.#name.ok_or(#msg)?),
+            }
+        });
+
+        let variant_cfgs = variant_cfgs(variant);
+        let variant_cfgs = &variant_cfgs;
+        builders.push(quote! {
+            #[allow(missing_docs)]
+            #( #variant_cfgs )*
+            #builder_vis struct #builder_name #generics #where_clause {
+                #( #decls, )*
+                __marker: ::core::marker::PhantomData<#enum_name #ty_generics>,
+            }
+
+            #( #variant_cfgs )*
+            #[allow(deprecated)]
+            #[automatically_derived]
+            impl #impl_generics #builder_name #ty_generics #where_clause {
+                #( #setters )*
+
+                #[allow(dead_code)]
+                #[allow(missing_docs)]
+                #vis fn build(self) -> ::core::result::Result<#enum_name #ty_generics, &'static ::core::primitive::str> {
+                    ::core::result::Result::Ok(#enum_name::#variant_name {
+                        #( #builds, )*
+                    })
+                }
+            }
+        });
+
+        let constructor_name = Ident::new(
+            &format!("{}_builder", variant_method_name(args, variant_name)),
+            Span::call_site(),
+        );
+        constructors.push(quote! {
+            #[inline]
+            #[allow(dead_code)]
+            #[allow(missing_docs)]
+            #( #variant_cfgs )*
+            #vis fn #constructor_name() -> #builder_name #ty_generics {
+                #builder_name {
+                    #( #inits, )*
+                    __marker: ::core::marker::PhantomData,
+                }
+            }
+        });
+    }
+
+    Ok(quote! {
+        #( #builders )*
+
+        #[allow(deprecated)]
+        #[automatically_derived]
+        impl #impl_generics #enum_name #ty_generics #where_clause {
+            #( #constructors )*
+        }
+    })
+}
+
+// Generics for a trait implementation which requires all types of common fields to implement the trait.
+// Types containing the enum itself are not bounded since the bound recursively requires the
+// implementation being defined.
+fn bounded_generics(input: &DeriveInput, shared: &[CommonField], bound: TokenStream2) -> Generics {
+    let mut generics = input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for field in shared.iter() {
+        let ty = &field.field.ty;
+        let mut idents = HashSet::new();
+        collect_idents(quote!(#ty), &mut idents);
+        if idents.contains(&input.ident.to_string()) {
+            continue;
+        }
+        where_clause.predicates.push(syn::parse_quote!(#ty: #bound));
+    }
+    generics
+}
+
+fn generate_partial_eq(args: &Args, input: &DeriveInput) -> TokenStream2 {
+    let enum_name = &input.ident;
+    let generics = bounded_generics(input, &args.fields, quote!(::core::cmp::PartialEq));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    // Each comparison is a statement so that #[cfg(...)] at the field can be put to it
+    let comparisons = args.fields.iter().map(|field| {
+        let accessor = field.accessor_name();
+        let cfgs = field.cfgs();
+        quote! {
+            #( #cfgs )*
+            {
+                if self.#accessor() != other.#accessor() {
+                    return false;
+                }
+            }
+        }
+    });
+
+    quote! {
+        #[allow(deprecated)]
+        #[automatically_derived]
+        impl #impl_generics ::core::cmp::PartialEq for #enum_name #ty_generics #where_clause {
+            fn eq(&self, other: &Self) -> ::core::primitive::bool {
+                #( #comparisons )*
+                true
+            }
+        }
+    }
+}
+
+fn generate_hash(args: &Args, input: &DeriveInput) -> TokenStream2 {
+    let enum_name = &input.ident;
+    let generics = bounded_generics(input, &args.fields, quote!(::core::hash::Hash));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let hashes = args.fields.iter().map(|field| {
+        let accessor = field.accessor_name();
+        let cfgs = field.cfgs();
+        quote! {
+            #( #cfgs )*
+            ::core::hash::Hash::hash(&self.#accessor(), state);
+        }
+    });
+
+    quote! {
+        #[allow(deprecated)]
+        #[automatically_derived]
+        impl #impl_generics ::core::hash::Hash for #enum_name #ty_generics #where_clause {
+            fn hash<__H: ::core::hash::Hasher>(&self, state: &mut __H) {
+                #( #hashes )*
+            }
+        }
+    }
+}
+
+// `PartialOrd` and `Ord` only see the key field. Equality is up to users since `PartialEq` and `Eq` should
+// be consistent with the ordering
+fn generate_ord(field: &CommonField, input: &DeriveInput) -> TokenStream2 {
+    let enum_name = &input.ident;
+    let generics = bounded_generics(input, std::slice::from_ref(field), quote!(::core::cmp::Ord));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let accessor = field.accessor_name();
+    let cfgs = field.cfgs().collect::<Vec<_>>();
+    let cfgs = &cfgs;
+
+    quote! {
+        #( #cfgs )*
+        #[allow(deprecated)]
+        #[automatically_derived]
+        impl #impl_generics ::core::cmp::PartialOrd for #enum_name #ty_generics #where_clause {
+            fn partial_cmp(&self, other: &Self) -> ::core::option::Option<::core::cmp::Ordering> {
+                ::core::option::Option::Some(::core::cmp::Ord::cmp(self, other))
+            }
+        }
+
+        #( #cfgs )*
+        #[allow(deprecated)]
+        #[automatically_derived]
+        impl #impl_generics ::core::cmp::Ord for #enum_name #ty_generics #where_clause {
+            fn cmp(&self, other: &Self) -> ::core::cmp::Ordering {
+                ::core::cmp::Ord::cmp(&self.#accessor(), &other.#accessor())
+            }
+        }
+    }
+}
+
+// `Display` writes the value of the field. Variants which don't have the `#[variant_optional]` field write
+// nothing unless the field has a default value with #[cow]
+fn generate_display(field: &CommonField, input: &DeriveInput) -> TokenStream2 {
+    let enum_name = &input.ident;
+    // Bounds are put only when the getter returns the field type itself
+    let generics = if field.deref || field.cow {
+        input.generics.clone()
+    } else {
+        bounded_generics(
+            input,
+            std::slice::from_ref(field),
+            quote!(::core::fmt::Display),
+        )
+    };
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let accessor = field.accessor_name();
+    let cfgs = field.cfgs();
+    let body = if field.variant_optional && !(field.cow && field.default.is_some()) {
+        quote! {
+            match self.#accessor() {
+                ::core::option::Option::Some(value) => ::core::fmt::Display::fmt(&value, f),
+                ::core::option::Option::None => ::core::result::Result::Ok(()),
+            }
+        }
+    } else {
+        quote!(::core::fmt::Display::fmt(&self.#accessor(), f))
+    };
+
+    quote! {
+        #( #cfgs )*
+        #[allow(deprecated)]
+        #[automatically_derived]
+        impl #impl_generics ::core::fmt::Display for #enum_name #ty_generics #where_clause {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                #body
+            }
+        }
+    }
+}
+
+// Common fields marked with #[pin] are structurally pinned by the pin projection accessors. As pin-project
+// crate does, the enum is `Unpin` only when all the pinned fields are `Unpin`, and implementing `Unpin`
+// or `Drop` for the enum is rejected with conflicting implementations since they could move the pinned
+// fields out.
+fn generate_pin_guards(args: &Args, input: &DeriveInput) -> TokenStream2 {
+    let pinned = args.fields.iter().filter(|f| f.pin).collect::<Vec<_>>();
+    if pinned.is_empty() {
+        return TokenStream2::new();
+    }
+    let enum_name = &input.ident;
+    let vis = &input.vis;
+    let decls = pinned.iter().map(|field| {
+        let name = field.name();
+        let ty = &field.field.ty;
+        let cfgs = field.cfgs();
+        quote!(#( #cfgs )* #name: #ty)
+    });
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    // The lifetime makes the bound of `Unpin` implementation not trivial even if the enum has no generic
+    // parameter. Trivial bounds such as `PhantomPinned: Unpin` are rejected by the compiler
+    let mut generics = input.generics.clone();
+    generics.params.insert(0, syn::parse_quote!('__pin));
+    generics.lt_token = Some(Default::default());
+    generics.gt_token = Some(Default::default());
+    let (pinned_impl_generics, pinned_ty_generics, _) = generics.split_for_impl();
+    let mut unpin_where = where_clause.cloned().unwrap_or_else(|| WhereClause {
+        where_token: Default::default(),
+        predicates: Punctuated::new(),
+    });
+    unpin_where.predicates.push(syn::parse_quote!(
+        __DiffEnumPinned #pinned_ty_generics: ::core::marker::Unpin
+    ));
+
+    quote! {
+        #[allow(deprecated)]
+        const _: () = {
+            #[allow(dead_code)]
+            #[doc(hidden)]
+            #vis struct __DiffEnumPinned #generics #where_clause {
+                __pin: ::core::marker::PhantomData<&'__pin ()>,
+                __enum: ::core::marker::PhantomData<fn() -> #enum_name #ty_generics>,
+                #( #decls, )*
+            }
+
+            #[automatically_derived]
+            impl #pinned_impl_generics ::core::marker::Unpin for #enum_name #ty_generics #unpin_where {}
+
+            trait __DiffEnumMustNotImplDrop {}
+            #[allow(drop_bounds)]
+            impl<T: ::core::ops::Drop> __DiffEnumMustNotImplDrop for T {}
+            impl #impl_generics __DiffEnumMustNotImplDrop for #enum_name #ty_generics #where_clause {}
+        };
+    }
+}
+
+// Accessors return the common field type so a field overriding the common field with `allow_override`
+// must have exactly the same type. Types cannot be compared by tokens since the same type can be written
+// in different ways such as `String` and `::std::string::String`, so the compiler checks it with a hidden
+// function which only compiles when the types are the same.
+fn generate_override_checks(args: &Args, input: &DeriveInput) -> Result<TokenStream2> {
+    let enum_name = &input.ident;
+    let variants = enum_variants(input)?;
+    let mut checks = vec![];
+    for (idx, variant) in variants.iter().enumerate() {
+        let fields = match variant.fields {
+            Fields::Named(ref f) => &f.named,
+            _ => continue,
+        };
+        for common in args.fields.iter() {
+            if !args.has_field(idx, common) {
+                continue;
+            }
+            let name = common.name();
+            let field = match fields.iter().find(|f| f.ident.as_ref() == Some(name)) {
+                Some(field) => field,
+                None => continue,
+            };
+            let expected = &common.field.ty;
+            let found = &field.ty;
+            let fn_name = Ident::new(
+                &format!(
+                    "__diff_enum_check_override_{}_{}",
+                    unraw(&variant.ident),
+                    unraw(name)
+                ),
+                Span::call_site(),
+            );
+            let cfgs = common.cfgs();
+            let variant_cfgs = variant_cfgs(variant);
+            let value = quote_spanned!(found.span()=> value);
+            checks.push(quote! {
+                #( #cfgs )*
+                #( #variant_cfgs )*
+                #[allow(dead_code, non_snake_case)]
+                #[doc(hidden)]
+                fn #fn_name(value: &#found) -> &#expected {
+                    #value
+                }
+            });
+        }
+    }
+    if checks.is_empty() {
+        return Ok(TokenStream2::new());
+    }
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        #[allow(deprecated)]
+        #[automatically_derived]
+        impl #impl_generics #enum_name #ty_generics #where_clause {
+            #( #checks )*
+        }
+    })
+}
+
+// Generates `Serialize` and `Deserialize` implementations which put common fields at the top level and
+// variant-specific fields in a nested object keyed by the variant name, such as
+// `{"user": "rhysd", "name": "foo", "GitHub": {"issues": 3}}`. They are implemented by delegating to
+// helper types which derive serde traits with `#[serde(flatten)]`.
+fn generate_serde(args: &Args, input: &DeriveInput) -> Result<TokenStream2> {
+    let enum_name = &input.ident;
+    let variants = enum_variants(input)?;
+    let generics = &input.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let self_ty = self_type(input);
+
+    // Helper types for serialization borrow fields of the enum value
+    let mut ser_generics = generics.clone();
+    ser_generics
+        .params
+        .insert(0, syn::parse_quote!('__diff_enum));
+    ser_generics.lt_token = Some(Default::default());
+    ser_generics.gt_token = Some(Default::default());
+    let (_, ser_ty_generics, _) = ser_generics.split_for_impl();
+
+    let mut ser_impl_generics = generics.clone();
+    let mut de_generics = generics.clone();
+    de_generics.params.insert(0, syn::parse_quote!('de));
+    de_generics.lt_token = Some(Default::default());
+    de_generics.gt_token = Some(Default::default());
+    for param in generics.params.iter() {
+        match *param {
+            GenericParam::Type(ref t) => {
+                let ident = &t.ident;
+                ser_impl_generics
+                    .make_where_clause()
+                    .predicates
+                    .push(syn::parse_quote!(#ident: ::serde::Serialize));
+                de_generics
+                    .make_where_clause()
+                    .predicates
+                    .push(syn::parse_quote!(#ident: ::serde::Deserialize<'de>));
+            }
+            GenericParam::Lifetime(ref l) => {
+                let lifetime = &l.lifetime;
+                de_generics
+                    .make_where_clause()
+                    .predicates
+                    .push(syn::parse_quote!('de: #lifetime));
+            }
+            GenericParam::Const(_) => {}
+        }
+    }
+    let (_, _, ser_where_clause) = ser_impl_generics.split_for_impl();
+    let (de_impl_generics, _, de_where_clause) = de_generics.split_for_impl();
+
+    let ser_fields = args.fields.iter().map(|field| {
+        let name = field.name();
+        let ty = &field.field.ty;
+        let cfgs = field.cfgs();
+        if field.variant_optional {
+            quote! {
+                #( #cfgs )*
+                #[serde(skip_serializing_if = "::core::option::Option::is_none")]
+                #name: ::core::option::Option<&'__diff_enum #ty>
+            }
+        } else {
+            quote!(#( #cfgs )* #name: &'__diff_enum #ty)
+        }
+    });
+    let de_fields = args.fields.iter().map(|field| {
+        let name = field.name();
+        let ty = &field.field.ty;
+        let cfgs = field.cfgs();
+        if field.variant_optional {
+            quote!(#( #cfgs )* #[serde(default)] #name: ::core::option::Option<#ty>)
+        } else {
+            quote!(#( #cfgs )* #name: #ty)
+        }
+    });
+
+    let mut ser_variants = vec![];
+    let mut de_variants = vec![];
+    let mut ser_arms = vec![];
+    let mut de_arms = vec![];
+    for (idx, variant) in variants.iter().enumerate() {
+        let ident = &variant.ident;
+        let variant_cfgs = variant_cfgs(variant);
+        let variant_cfgs = &variant_cfgs;
+        let own_fields = variant_specific_fields(args, variant);
+        let field_cfgs = |field: &Field| {
+            field
+                .attrs
+                .iter()
+                .filter(|attr| attr.path.is_ident("cfg"))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        let own_tys = own_fields
+            .iter()
+            .map(|field| replace_self(&field.ty, &self_ty))
+            .collect::<Vec<_>>();
+
+        // Bindings of the variant-specific fields are shared by the enum and the helper variants.
+        // `own_inits` initializes the fields of the enum variant with the bindings.
+        let (own_pats, ser_decl, de_decl, bindings, own_inits) = match variant.fields {
+            Fields::Unnamed(_) => {
+                // Positional fields of tuple variant are shifted when common fields are prepended
+                let shift = match args.variants[idx].tuple_offset {
+                    Some(0) if args.prepend => args
+                        .fields
+                        .iter()
+                        .filter(|f| args.has_field(idx, f))
+                        .count(),
+                    _ => 0,
+                };
+                let bindings = (0..own_fields.len())
+                    .map(|i| Ident::new(&format!("__field{}", i), Span::call_site()))
+                    .collect::<Vec<_>>();
+                let bindings = &bindings;
+                let indices = (0..own_fields.len())
+                    .map(|i| syn::Index::from(i + shift))
+                    .collect::<Vec<_>>();
+                let indices = &indices;
+                let own_tys = &own_tys;
+                (
+                    quote!(#( #indices: ref #bindings, )*),
+                    quote!(( #( &'__diff_enum #own_tys, )* )),
+                    quote!(( #( #own_tys, )* )),
+                    quote!(( #( #bindings, )* )),
+                    quote!(#( #indices: #bindings, )*),
+                )
+            }
+            _ => {
+                let names = own_fields.iter().map(|f| &f.ident).collect::<Vec<_>>();
+                let cfgs = own_fields.iter().map(|f| field_cfgs(f)).collect::<Vec<_>>();
+                let (names, cfgs, own_tys) = (&names, &cfgs, &own_tys);
+                (
+                    quote!(#( #( #cfgs )* ref #names, )*),
+                    quote!({ #( #( #cfgs )* #names: &'__diff_enum #own_tys, )* }),
+                    quote!({ #( #( #cfgs )* #names: #own_tys, )* }),
+                    quote!({ #( #( #cfgs )* #names, )* }),
+                    quote!(#( #( #cfgs )* #names, )*),
+                )
+            }
+        };
+        ser_variants.push(quote!(#( #variant_cfgs )* #ident #ser_decl));
+        de_variants.push(quote!(#( #variant_cfgs )* #ident #de_decl));
+
+        let common_pats = args
+            .fields
+            .iter()
+            .filter(|f| args.has_field(idx, f))
+            .map(|field| {
+                let cfgs = field.cfgs();
+                let pat = args.field_pattern(idx, field, quote!(ref));
+                quote!(#( #cfgs )* #pat)
+            });
+        let common_values = args.fields.iter().map(|field| {
+            let name = field.name();
+            let cfgs = field.cfgs();
+            let has_field = args.has_field(idx, field);
+            match (field.variant_optional, has_field) {
+                (true, true) => quote!(#( #cfgs )* #name: ::core::option::Option::Some(#name)),
+                (true, false) => quote!(#( #cfgs )* #name: ::core::option::Option::None),
+                _ => quote!(#( #cfgs )* #name),
+            }
+        });
+        ser_arms.push(quote! {
+            #( #variant_cfgs )*
+            #enum_name::#ident{ #own_pats #( #common_pats, )* .. } => __DiffEnumSer {
+                #( #common_values, )*
+                __variant: __DiffEnumSerVariant::#ident #bindings,
+            },
+        });
+
+        let common_inits = args
+            .fields
+            .iter()
+            .filter(|f| args.has_field(idx, f))
+            .map(|field| {
+                let name = field.name();
+                let cfgs = field.cfgs();
+                let member = args.member(idx, field);
+                let value = if field.variant_optional {
+                    let key = name.to_string();
+                    quote! {
+                        match __value.#name {
+                            ::core::option::Option::Some(__v) => __v,
+                            ::core::option::Option::None => {
+                                return ::core::result::Result::Err(
+                                    <__D::Error as ::serde::de::Error>::missing_field(#key),
+                                )
+                            }
+                        }
+                    }
+                } else {
+                    quote!(__value.#name)
+                };
+                quote!(#( #cfgs )* #member: #value)
+            });
+        de_arms.push(quote! {
+            #( #variant_cfgs )*
+            __DiffEnumDeVariant::#ident #bindings => #enum_name::#ident {
+                #own_inits
+                #( #common_inits, )*
+            },
+        });
+    }
+
+    let ser = quote! {
+        #[derive(::serde::Serialize)]
+        struct __DiffEnumSer #ser_generics #where_clause {
+            #( #ser_fields, )*
+            #[serde(flatten)]
+            __variant: __DiffEnumSerVariant #ser_ty_generics,
+        }
+
+        #[derive(::serde::Serialize)]
+        #[allow(dead_code)]
+        enum __DiffEnumSerVariant #ser_generics #where_clause {
+            #( #ser_variants, )*
+            // Makes all generic parameters used. This variant is never constructed
+            #[serde(skip)]
+            __DiffEnumMarker(
+                ::core::marker::PhantomData<&'__diff_enum #self_ty>,
+                ::core::convert::Infallible,
+            ),
+        }
+    };
+    let de = quote! {
+        #[derive(::serde::Deserialize)]
+        struct __DiffEnumDe #generics #where_clause {
+            #( #de_fields, )*
+            #[serde(flatten)]
+            __variant: __DiffEnumDeVariant #ty_generics,
+        }
+
+        #[derive(::serde::Deserialize)]
+        #[allow(dead_code)]
+        enum __DiffEnumDeVariant #generics #where_clause {
+            #( #de_variants, )*
+            #[serde(skip)]
+            __DiffEnumMarker(
+                ::core::marker::PhantomData<#self_ty>,
+                ::core::convert::Infallible,
+            ),
+        }
+    };
+    let ser_impl = quote! {
+        #[automatically_derived]
+        impl #impl_generics ::serde::Serialize for #enum_name #ty_generics #ser_where_clause {
+            fn serialize<__S: ::serde::Serializer>(
+                &self,
+                serializer: __S,
+            ) -> ::core::result::Result<__S::Ok, __S::Error> {
+                let value = match self {
+                    #( #ser_arms )*
+                };
+                ::serde::Serialize::serialize(&value, serializer)
+            }
+        }
+    };
+    let de_impl = quote! {
+        #[automatically_derived]
+        impl #de_impl_generics ::serde::Deserialize<'de> for #enum_name #ty_generics #de_where_clause {
+            fn deserialize<__D: ::serde::Deserializer<'de>>(
+                deserializer: __D,
+            ) -> ::core::result::Result<Self, __D::Error> {
+                let __value = <__DiffEnumDe #ty_generics as ::serde::Deserialize<'de>>::deserialize(deserializer)?;
+                ::core::result::Result::Ok(match __value.__variant {
+                    #( #de_arms )*
+                    __DiffEnumDeVariant::__DiffEnumMarker(_, never) => match never {},
+                })
+            }
+        }
+    };
+
+    // Helper types are hidden in the anonymous const block
+    Ok(quote! {
+        #[allow(deprecated)]
+        const _: () = {
+            #ser
+            #de
+            #ser_impl
+            #de_impl
+        };
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::ToTokens;
+    use syn::{File, ImplItem, Item, ItemEnum, Member, Pat, ReturnType, Stmt};
+
+    fn expand(attr: TokenStream2, item: TokenStream2) -> File {
+        let tokens = expand_common_fields(attr, item).unwrap();
+        syn::parse2(tokens).unwrap()
+    }
+
+    fn expand_err(attr: TokenStream2, item: TokenStream2) -> String {
+        match expand_common_fields(attr, item) {
+            Ok(tokens) => panic!("Unexpected success: {}", tokens),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    fn field_names(fields: &Fields) -> Vec<String> {
+        fields
+            .iter()
+            .map(|f| f.ident.as_ref().unwrap().to_string())
+            .collect()
+    }
+
+    fn expanded_enum(file: &File) -> &ItemEnum {
+        match file.items[0] {
+            Item::Enum(ref e) => e,
+            _ => panic!("First item is not an enum"),
+        }
+    }
+
+    fn method_names(file: &File) -> Vec<String> {
+        let mut names = vec![];
+        for item in file.items.iter() {
+            if let Item::Impl(ref i) = *item {
+                for item in i.items.iter() {
+                    if let ImplItem::Method(ref m) = *item {
+                        names.push(m.sig.ident.to_string());
+                    }
+                }
+            }
+        }
+        names
+    }
+
+    fn find_method<'a>(file: &'a File, name: &str) -> &'a ImplItemMethod {
+        for item in file.items.iter() {
+            if let Item::Impl(ref i) = *item {
+                for item in i.items.iter() {
+                    if let ImplItem::Method(ref m) = *item {
+                        if m.sig.ident == name {
+                            return m;
+                        }
+                    }
+                }
+            }
+        }
+        panic!("Method '{}' is not found", name)
+    }
+
+    #[test]
+    fn inject_common_fields() {
+        let file = expand(
+            quote!(x: i32, y: u32),
+            quote!(
+                enum E {
+                    A { b: bool },
+                    B,
+                }
+            ),
+        );
+        let e = expanded_enum(&file);
+        let fields: Vec<_> = e.variants.iter().map(|v| field_names(&v.fields)).collect();
+        assert_eq!(fields, vec![vec!["b", "x", "y"], vec!["x", "y"]]);
+    }
+
+    #[test]
+    fn prepend_common_fields() {
+        let file = expand(
+            quote!(prepend, { x: i32, y: u32 }),
+            quote!(
+                enum E {
+                    A { b: bool },
+                }
+            ),
+        );
+        let e = expanded_enum(&file);
+        assert_eq!(field_names(&e.variants[0].fields), vec!["x", "y", "b"]);
+    }
+
+    #[test]
+    fn preserve_enum_attributes() {
+        let file = expand(
+            quote!(x: i32),
+            quote! {
+                #[non_exhaustive]
+                #[my_attr(foo)]
+                #[derive(Debug)]
+                enum E { A }
+            },
+        );
+        let e = expanded_enum(&file);
+        let attrs: Vec<_> = e
+            .attrs
+            .iter()
+            .map(|a| a.into_token_stream().to_string())
+            .collect();
+        let expected: Vec<_> = [
+            quote!(#[non_exhaustive]),
+            quote!(#[my_attr(foo)]),
+            quote!(#[derive(Debug)]),
+        ]
+        .iter()
+        .map(|t| t.to_string())
+        .collect();
+        assert_eq!(attrs, expected);
+    }
+
+    #[test]
+    fn generate_accessor_methods() {
+        let file = expand(
+            quote!(x: i32),
+            quote!(
+                enum E {
+                    A,
+                    B,
+                }
+            ),
+        );
+        assert_eq!(
+            method_names(&file),
+            vec![
+                "x",
+                "is_a",
+                "is_b",
+                "variant_name",
+                "common_field_names",
+                "__diff_enum_common_match_x"
+            ]
+        );
+    }
+
+    #[test]
+    fn accessors_option() {
+        let combinations: &[(&str, &[&str])] = &[
+            ("get", &["x"]),
+            ("set", &["set_x", "replace_x"]),
+            ("mut", &["x_mut"]),
+            ("get,set", &["x", "set_x", "replace_x"]),
+            ("get,mut", &["x", "x_mut"]),
+            ("set,mut", &["x_mut", "set_x", "replace_x"]),
+            ("get, set, mut", &["x", "x_mut", "set_x", "replace_x"]),
+        ];
+        for (kinds, expected) in combinations {
+            let file = expand(
+                quote!(accessors = #kinds, { x: i32, #[variant_optional] y: u32 }),
+                quote!(
+                    enum E {
+                        A,
+                    }
+                ),
+            );
+            let names = method_names(&file)
+                .into_iter()
+                .filter(|n| !n.starts_with("__") && (n.contains('x') || n.contains('y')))
+                .collect::<Vec<_>>();
+            // Setter and `replace_x()` are never defined for optional fields
+            let mut want = expected.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+            want.extend(
+                expected
+                    .iter()
+                    .filter(|n| !n.starts_with("set_") && !n.starts_with("replace_"))
+                    .map(|n| n.replace('x', "y")),
+            );
+            assert_eq!(names, want, "{}", kinds);
+        }
+
+        let msg = expand_err(
+            quote!(accessors = "get,ref", { x: i32 }),
+            quote!(
+                enum E {
+                    A,
+                }
+            ),
+        );
+        assert!(
+            msg.contains("Unknown accessor 'ref' for accessors option"),
+            "{}",
+            msg
+        );
+
+        let msg = expand_err(
+            quote!(accessors = "set", partial_eq, { x: i32 }),
+            quote!(
+                enum E {
+                    A,
+                }
+            ),
+        );
+        assert!(
+            msg.contains("'partial_eq' cannot be used without getters"),
+            "{}",
+            msg
+        );
+    }
+
+    #[test]
+    fn forward_field_docs_to_getter() {
+        let file = expand(
+            quote!(accessors = "get,set,mut", {
+                /// Number of stars
+                #[cfg(feature = "stars")]
+                stars: u32,
+            }),
+            quote!(
+                enum E {
+                    A,
+                }
+            ),
+        );
+        let attrs = |name| {
+            find_method(&file, name)
+                .attrs
+                .iter()
+                .map(|a| a.into_token_stream().to_string())
+                .collect::<Vec<_>>()
+        };
+        let getter = attrs("stars");
+        assert!(
+            getter.iter().any(|a| a.contains("Number of stars")),
+            "{:?}",
+            getter
+        );
+        assert!(getter.iter().any(|a| a.contains("cfg")), "{:?}", getter);
+        let setter = attrs("set_stars");
+        assert!(
+            !setter.iter().any(|a| a.contains("Number of stars")),
+            "{:?}",
+            setter
+        );
+        assert!(setter.iter().any(|a| a.contains("cfg")), "{:?}", setter);
+    }
+
+    #[test]
+    fn document_variants_of_accessors() {
+        let file = expand(
+            quote!(accessors = "get,set,mut", {
+                /// Name of user
+                user: String,
+                #[variant_optional]
+                stars: u32,
+            }),
+            quote!(
+                enum Repo {
+                    GitHub,
+                    GitLab,
+                    #[common_fields(skip(stars))]
+                    Local,
+                }
+            ),
+        );
+        let docs = |name| {
+            find_method(&file, name)
+                .attrs
+                .iter()
+                .filter_map(|a| match a.parse_meta() {
+                    Ok(Meta::NameValue(MetaNameValue {
+                        ref ident,
+                        lit: Lit::Str(ref s),
+                        ..
+                    })) if ident == "doc" => Some(s.value()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let all = "Common field available on all variants: GitHub, GitLab, Local.";
+        assert_eq!(docs("user"), vec![" Name of user", "", all]);
+        assert_eq!(docs("user_mut"), vec![all]);
+        assert_eq!(docs("set_user"), vec![all]);
+        let some = "Common field available on variants: GitHub, GitLab.";
+        assert_eq!(docs("stars"), vec![some]);
+        assert_eq!(docs("stars_mut"), vec![some]);
+    }
+
+    #[test]
+    fn associated_type_field() {
+        let file = expand(
+            quote!(accessors = "get,set,mut", where T: Iterator, { x: <T as Iterator>::Item }),
+            quote!(
+                enum E<T> {
+                    A,
+                }
+            ),
+        );
+        let ret = |name| match find_method(&file, name).sig.decl.output {
+            ReturnType::Type(_, ref ty) => ty.into_token_stream().to_string(),
+            ReturnType::Default => panic!("No return type"),
+        };
+        let expected: Type = syn::parse_quote!(<T as Iterator>::Item);
+        let expected = expected.into_token_stream().to_string();
+        assert_eq!(ret("x"), format!("& {}", expected));
+        assert_eq!(ret("x_mut"), format!("& mut {}", expected));
+    }
+
+    #[test]
+    fn complex_field_types() {
+        let file = expand(
+            quote!(accessors = "get,set,mut", {
+                f: fn(u32) -> u32,
+                t: (u8, &'static str),
+                a: [i32; 3],
+                s: &'static [u8],
+            }),
+            quote!(
+                enum E {
+                    A,
+                }
+            ),
+        );
+        let ret = |name: &str| match find_method(&file, name).sig.decl.output {
+            ReturnType::Type(_, ref ty) => ty.into_token_stream().to_string(),
+            ReturnType::Default => panic!("No return type"),
+        };
+        let types: &[(&str, Type)] = &[
+            ("f", syn::parse_quote!(fn(u32) -> u32)),
+            ("t", syn::parse_quote!((u8, &'static str))),
+            ("a", syn::parse_quote!([i32; 3])),
+            ("s", syn::parse_quote!(&'static [u8])),
+        ];
+        for (name, ty) in types {
+            let expected = ty.into_token_stream().to_string();
+            assert_eq!(ret(name), format!("& {}", expected));
+            assert_eq!(ret(&format!("{}_mut", name)), format!("& mut {}", expected));
+        }
+    }
+
+    #[test]
+    fn automatically_derived_impls() {
+        let is_derived = |i: &syn::ItemImpl| {
+            i.attrs
+                .iter()
+                .any(|a| a.path.is_ident("automatically_derived"))
+        };
+        let file = expand(
+            quote!(partial_eq, hash, common_struct, default = "A", { #[ord_key] #[display] x: i32 }),
+            quote!(
+                enum E {
+                    A,
+                }
+            ),
+        );
+        let mut impls = 0;
+        for item in file.items.iter() {
+            if let Item::Impl(ref i) = *item {
+                assert!(is_derived(i), "{}", i.into_token_stream());
+                impls += 1;
+            }
+        }
+        assert!(impls > 5, "{}", impls);
+
+        // Inherent impl blocks are not marked so that unused methods are warned
+        let file = expand(
+            quote!(warn_unused, trait = "HasX", { x: i32 }),
+            quote!(
+                enum E {
+                    A,
+                }
+            ),
+        );
+        for item in file.items.iter() {
+            if let Item::Impl(ref i) = *item {
+                assert_eq!(
+                    is_derived(i),
+                    i.trait_.is_some(),
+                    "{}",
+                    i.into_token_stream()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn with_common_constructors() {
+        let file = expand(
+            quote!(common_struct, { x: i32, #[variant_optional] y: u32 }),
+            quote!(
+                enum E {
+                    #[common_fields(skip(y))]
+                    A {
+                        a: bool,
+                    },
+                    B,
+                }
+            ),
+        );
+        let names = method_names(&file)
+            .into_iter()
+            .filter(|n| n.ends_with("_with_common"))
+            .collect::<Vec<_>>();
+        // B has the optional field which may be `None` in the common struct
+        assert_eq!(names, vec!["a_with_common"]);
+    }
+
+    #[test]
+    fn lifetime_and_type_generics_in_impl() {
+        let file = expand(
+            quote!({ label: &'a str, data: T }),
+            quote!(
+                enum E<'a, T: Clone + 'a> {
+                    A { r: &'a T },
+                }
+            ),
+        );
+        let imp = file
+            .items
+            .iter()
+            .filter_map(|item| match *item {
+                Item::Impl(ref i) if i.trait_.is_none() => Some(i),
+                _ => None,
+            })
+            .next()
+            .unwrap();
+        let generics = (&imp.generics).into_token_stream().to_string();
+        assert_eq!(generics, quote!(<'a, T: Clone + 'a>).to_string());
+        let self_ty = (&imp.self_ty).into_token_stream().to_string();
+        assert_eq!(self_ty, quote!(E<'a, T>).to_string());
+
+        let ret = match find_method(&file, "label").sig.decl.output {
+            ReturnType::Type(_, ref ty) => ty.into_token_stream().to_string(),
+            ReturnType::Default => panic!("No return type"),
+        };
+        let expected: Type = syn::parse_quote!(&'a str);
+        let expected = expected.into_token_stream().to_string();
+        assert_eq!(ret, format!("& {}", expected));
+    }
+
+    #[test]
+    fn error_on_stacked_attribute_without_arguments() {
+        for stacked in &[quote!(#[common_fields]), quote!(#[common_fields = "y"])] {
+            let msg = expand_err(
+                quote!({ x: i32 }),
+                quote!(
+                    #stacked
+                    enum E {
+                        A,
+                    }
+                ),
+            );
+            assert!(
+                msg.contains("Stacked #[diff_enum::common_fields] must have its arguments"),
+                "{}",
+                msg
+            );
+        }
+    }
+
+    #[test]
+    fn error_on_no_common_field() {
+        let msg = expand_err(
+            quote!(),
+            quote!(
+                enum E {
+                    A,
+                }
+            ),
+        );
+        assert!(msg.contains("No shared field"), "{}", msg);
+    }
+
+    #[test]
+    fn error_on_non_enum() {
+        let msg = expand_err(
+            quote!(x: i32),
+            quote!(
+                struct S {
+                    y: i32,
+                }
+            ),
+        );
+        assert!(msg.contains("can be set at only enum"), "{}", msg);
+    }
+
+    #[test]
+    fn error_on_tuple_variant() {
+        let msg = expand_err(
+            quote!(x: i32),
+            quote!(
+                enum E {
+                    A(i32),
+                }
+            ),
+        );
+        assert!(
+            msg.contains("cannot mix named fields with unnamed fields"),
+            "{}",
+            msg
+        );
+    }
+
+    #[test]
+    fn inline_option() {
+        for (value, expected) in &[
+            ("always", Some("# [ inline ( always ) ]")),
+            ("never", Some("# [ inline ( never ) ]")),
+            ("hint", Some("# [ inline ]")),
+            ("none", None),
+        ] {
+            let file = expand(
+                quote!(inline = #value, accessors = "get,set,mut", { x: i32 }),
+                quote!(
+                    enum E {
+                        A,
+                    }
+                ),
+            );
+            for name in &["x", "x_mut", "set_x", "is_a"] {
+                let attrs: Vec<_> = find_method(&file, name)
+                    .attrs
+                    .iter()
+                    .map(|a| a.into_token_stream().to_string())
+                    .filter(|a| a.contains("inline"))
+                    .collect();
+                match expected {
+                    Some(e) => assert_eq!(attrs, vec![e.to_string()], "{}", value),
+                    None => assert!(attrs.is_empty(), "{:?}", attrs),
+                }
+            }
+        }
+
+        let msg = expand_err(
+            quote!(inline = "sometimes", { x: i32 }),
+            quote!(
+                enum E {
+                    A,
+                }
+            ),
+        );
+        assert!(
+            msg.contains("Unknown value 'sometimes' for inline option"),
+            "{}",
+            msg
+        );
+    }
+
+    #[test]
+    fn no_accessors_option() {
+        let file = expand(
+            quote!(no_accessors, kind, { x: i32 }),
+            quote!(
+                enum E {
+                    A,
+                }
+            ),
+        );
+        assert_eq!(method_names(&file), vec!["kind"]);
+        let e = expanded_enum(&file);
+        assert_eq!(field_names(&e.variants[0].fields), vec!["x"]);
+
+        let msg = expand_err(
+            quote!(no_accessors, partial_eq, { x: i32 }),
+            quote!(
+                enum E {
+                    A,
+                }
+            ),
+        );
+        assert!(
+            msg.contains("'partial_eq' option cannot be used with 'no_accessors'"),
+            "{}",
+            msg
+        );
+    }
+
+    #[test]
+    fn method_name_collision() {
+        let item = quote!(
+            enum E {
+                A,
+                B,
+            }
+        );
+        let msg = expand_err(quote!({ is_a: bool }), item.clone());
+        assert!(
+            msg.contains("Method 'is_a' generated for common field 'is_a' conflicts with the method generated by predicate of variant 'A'"),
+            "{}",
+            msg
+        );
+        assert!(!msg.contains("disable the option"), "{}", msg);
+
+        let msg = expand_err(
+            quote!(accessors = "get,mut", { x: i32, x_mut: i32 }),
+            item.clone(),
+        );
+        assert!(
+            msg.contains("Method 'x_mut' generated for common field 'x_mut' conflicts with the method generated by common field 'x'"),
+            "{}",
+            msg
+        );
+
+        let msg = expand_err(quote!(into, common_struct, { common: i32 }), item.clone());
+        assert!(msg.contains("'common_struct' option"), "{}", msg);
+
+        // Renaming the accessor resolves the collision
+        let file = expand(
+            quote!(kind, { #[accessor(name = "kind_name")] kind: i32 }),
+            item,
+        );
+        let names = method_names(&file);
+        assert!(names.contains(&"kind".to_string()), "{:?}", names);
+        assert!(names.contains(&"kind_name".to_string()), "{:?}", names);
+    }
+
+    #[test]
+    fn option_without_value() {
+        let item = quote!(
+            enum E {
+                A,
+            }
+        );
+        for option in &["vis", "inline", "default", "impl_trait"] {
+            let option = Ident::new(option, Span::call_site());
+            let msg = expand_err(quote!(#option, { x: i32 }), item.clone());
+            let want = format!(
+                "Option '{}' requires a string value in the form of `{} = \"...\"`",
+                option, option
+            );
+            assert!(msg.contains(&want), "{}", msg);
+            assert!(!msg.contains("Cannot parse fields"), "{}", msg);
+        }
+        let msg = expand_err(quote!(trait = 1, { x: i32 }), item);
+        assert!(
+            msg.contains("Option 'trait' requires a string value"),
+            "{}",
+            msg
+        );
+    }
+
+    #[test]
+    fn accessor_spans() {
+        // Tokens parsed from source text have locations while tokens by quote! don't
+        let attr: TokenStream2 = "accessors = \"get,set,mut\", {\n    user: String,\n}"
+            .parse()
+            .unwrap();
+        let item: TokenStream2 = "enum E {\n    A,\n    GitHub,\n}".parse().unwrap();
+        let file = expand(attr, item);
+
+        for name in &["user", "user_mut", "set_user"] {
+            let start = find_method(&file, name).sig.ident.span().start();
+            assert_eq!((start.line, start.column), (2, 4), "{}", name);
+        }
+        let start = find_method(&file, "is_git_hub").sig.ident.span().start();
+        assert_eq!((start.line, start.column), (3, 4));
+    }
+
+    #[test]
+    fn const_option() {
+        let item = quote!(
+            enum E {
+                A,
+            }
+        );
+        let file = expand(
+            quote!(const, accessors = "get,set,mut", { x: i32 }),
+            item.clone(),
+        );
+        assert!(find_method(&file, "x").sig.constness.is_some());
+        assert!(find_method(&file, "is_a").sig.constness.is_some());
+        assert!(find_method(&file, "x_mut").sig.constness.is_none());
+        assert!(find_method(&file, "set_x").sig.constness.is_none());
+
+        let msg = expand_err(quote!(const, trait = "T", { x: i32 }), item);
+        assert!(
+            msg.contains("'const' option cannot be used with 'trait' option"),
+            "{}",
+            msg
+        );
+    }
+
+    #[test]
+    fn no_wildcard_arm() {
+        // Wildcard arm would hide unhandled variants and cause unreachable_patterns lint
+        let file = expand(
+            quote!(into, kind, common_struct, { x: i32 }),
+            quote!(
+                #[non_exhaustive]
+                enum E {
+                    A,
+                    B { b: bool },
+                }
+            ),
+        );
+        let s = file.into_token_stream().to_string();
+        assert!(!s.contains("_ =>"), "{}", s);
+    }
+
+    #[test]
+    fn one_arm_per_variant() {
+        let variants = (0..50).map(|i| {
+            let name = Ident::new(&format!("V{}", i), Span::call_site());
+            match i % 3 {
+                0 => quote!(#[common_fields(skip(y))] #name),
+                1 => quote!(#name { a: bool }),
+                _ => quote!(#name),
+            }
+        });
+        let file = expand(
+            quote!(into, common_refs, accessors = "get,set,mut", { x: i32, #[variant_optional] y: u32 }),
+            quote!(
+                enum E {
+                    #( #variants, )*
+                }
+            ),
+        );
+
+        let mut checked = 0;
+        for item in file.items.iter() {
+            let i = match *item {
+                Item::Impl(ref i) => i,
+                _ => continue,
+            };
+            for item in i.items.iter() {
+                let method = match *item {
+                    ImplItem::Method(ref m) => m,
+                    _ => continue,
+                };
+                let name = method.sig.ident.to_string();
+                let arms = match method.block.stmts.last() {
+                    Some(Stmt::Expr(Expr::Match(ref m))) => &m.arms,
+                    // Pin projection is wrapped with unsafe block
+                    _ => continue,
+                };
+                assert_eq!(arms.len(), 50, "{}", name);
+                for (idx, arm) in arms.iter().enumerate() {
+                    let pats = arm.pats.iter().collect::<Vec<_>>();
+                    assert_eq!(pats.len(), 1, "{}", name);
+                    let pat = match *pats[0] {
+                        Pat::Struct(ref p) => p,
+                        ref p => panic!(
+                            "Unexpected pattern {:?} in {}",
+                            quote!(#p).to_string(),
+                            name
+                        ),
+                    };
+                    let variant = &pat.path.segments.last().unwrap().into_value().ident;
+                    assert_eq!(variant.to_string(), format!("V{}", idx), "{}", name);
+
+                    // Accessors of a common field must bind the field in all variants which have it
+                    let field = if name.starts_with('x') || name.ends_with("_x") {
+                        "x"
+                    } else if (name.starts_with('y') || name.ends_with("_y")) && idx % 3 != 0 {
+                        "y"
+                    } else {
+                        continue;
+                    };
+                    let bound = pat.fields.iter().any(|f| match f.member {
+                        Member::Named(ref i) => i == field,
+                        Member::Unnamed(_) => false,
+                    });
+                    assert!(
+                        bound,
+                        "'{}' is not bound in arm of V{} in {}",
+                        field, idx, name
+                    );
+                }
+                checked += 1;
+            }
+        }
+        // x, x_mut, set_x, replace_x, into_x, y, y_mut, into_y, common_refs, variant_name, 50 predicates
+        // and 2 hidden methods for common_match!
+        assert_eq!(checked, 62);
+    }
+
+    #[test]
+    fn common_method_impl_trait_return_type() {
+        let tokens = expand_common_methods(
+            quote! {
+                fn lens<'a>(&'a self) -> impl Iterator<Item = usize> + 'a {
+                    self.names().iter().map(String::len)
+                }
+            },
+            quote!(
+                enum E {
+                    A { names: Vec<String> },
+                }
+            ),
+        )
+        .unwrap();
+        let file: File = syn::parse2(tokens).unwrap();
+        let ret = match find_method(&file, "lens").sig.decl.output {
+            ReturnType::Type(_, ref ty) => ty.into_token_stream().to_string(),
+            ReturnType::Default => panic!("No return type"),
+        };
+        let expected: Type = syn::parse_quote!(impl Iterator<Item = usize> + 'a);
+        assert_eq!(ret, expected.into_token_stream().to_string());
+    }
+
+    #[test]
+    fn expand_common_match_macro() {
+        let tokens = expand_common_match(quote!(repo, { user, name } => (user, name))).unwrap();
+        let expr: Expr = syn::parse2(tokens).unwrap();
+        let s = expr.into_token_stream().to_string();
+        assert!(
+            s.contains("__diff_enum_value . __diff_enum_common_match_user ( )"),
+            "{}",
+            s
+        );
+        assert!(s.contains("( user , name , ) => ( user , name )"), "{}", s);
+
+        let err = expand_common_match(quote!(repo, {} => 0)).unwrap_err();
+        assert!(err.to_string().contains("No common field"), "{}", err);
+        let err = expand_common_match(quote!(repo => 0)).unwrap_err();
+        assert!(
+            err.to_string().contains("Cannot parse arguments"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn error_on_cfg_field_in_tuple_variant() {
+        let msg = expand_err(
+            quote!(tuple, { #[cfg(test)] x: i32 }),
+            quote!(
+                enum E {
+                    A(i32),
+                }
+            ),
+        );
+        assert!(
+            msg.contains("cannot be injected into tuple variant A"),
+            "{}",
+            msg
+        );
+    }
+
+    #[test]
+    fn error_on_unknown_option() {
+        let msg = expand_err(
+            quote!(foo, { x: i32 }),
+            quote!(
+                enum E {
+                    A,
+                }
+            ),
+        );
+        assert!(msg.contains("Unknown option 'foo'"), "{}", msg);
+    }
+
+    #[test]
+    fn error_on_multiple_ord_keys() {
+        let msg = expand_err(
+            quote!({ #[ord_key] x: i32, y: i32, #[ord_key] z: i32 }),
+            quote!(
+                enum E {
+                    A,
+                }
+            ),
+        );
+        assert!(
+            msg.contains("#[ord_key] is put to more than one common field 'x' and 'z'"),
+            "{}",
+            msg
+        );
+    }
+
+    #[test]
+    fn error_on_multiple_display_fields() {
+        let msg = expand_err(
+            quote!({ #[display] x: String, #[display] y: String }),
+            quote!(
+                enum E {
+                    A,
+                }
+            ),
+        );
+        assert!(
+            msg.contains("#[display] is put to more than one common field 'x' and 'y'"),
+            "{}",
+            msg
+        );
+    }
+
+    #[test]
+    fn error_on_unknown_group() {
+        let msg = expand_err(
+            quote!({ #[group(remote)] x: i32 }),
+            quote!(
+                enum E {
+                    #[groups(remote, local)]
+                    A,
+                }
+            ),
+        );
+        assert!(
+            msg.contains("Group 'local' at enum variant A is not given to any common field"),
+            "{}",
+            msg
+        );
+
+        let msg = expand_err(
+            quote!({ #[group] x: i32 }),
+            quote!(
+                enum E {
+                    A,
+                }
+            ),
+        );
+        assert!(msg.contains("#[group] must be in the form of"), "{}", msg);
+    }
+
+    #[test]
+    fn error_on_invalid_prefix() {
+        let msg = expand_err(
+            quote!(prefix = "1", { x: i32 }),
+            quote!(
+                enum E {
+                    A,
+                }
+            ),
+        );
+        assert!(
+            msg.contains("Prefix \"1\" given to prefix option makes invalid field name '1x'"),
+            "{}",
+            msg
+        );
+    }
+
+    #[test]
+    fn error_on_cow_field_with_shared() {
+        let msg = expand_err(
+            quote!(shared, { #[cow] x: String }),
+            quote!(
+                enum E {
+                    A,
+                }
+            ),
+        );
+        assert!(
+            msg.contains("Common field 'x' with #[cow] cannot be used with 'shared' option"),
+            "{}",
+            msg
+        );
+    }
+
+    #[test]
+    fn error_on_derive_helper_without_derive() {
+        let msg = expand_err(
+            quote!({ x: i32 }),
+            quote!(
+                enum E {
+                    #[default]
+                    A,
+                }
+            ),
+        );
+        assert!(
+            msg.contains("#[default] is found but #[derive(Default)] is not put below"),
+            "{}",
+            msg
+        );
+
+        // The derive is put below the attribute
+        for derive in &[
+            quote!(#[derive(Debug, Default)]),
+            quote!(#[cfg_attr(test, derive(Default))]),
+        ] {
+            expand(
+                quote!({ x: i32 }),
+                quote!(
+                    #derive
+                    enum E {
+                        #[default]
+                        A,
+                    }
+                ),
+            );
+        }
+    }
+
+    #[test]
+    fn error_on_deref_field_with_shared() {
+        let msg = expand_err(
+            quote!(shared, { #[deref] x: String }),
+            quote!(
+                enum E {
+                    A,
+                }
+            ),
+        );
+        assert!(
+            msg.contains("Common field 'x' with #[deref] cannot be used with 'shared' option"),
+            "{}",
+            msg
+        );
+    }
+
+    #[test]
+    fn skip_variant_optional_field() {
+        let file = expand(
+            quote!({ x: i32, #[variant_optional] y: u32 }),
+            quote!(
+                enum E {
+                    #[common_fields(skip(y))]
+                    A {
+                        a: bool,
+                    },
+                    B,
+                }
+            ),
+        );
+        let e = expanded_enum(&file);
+        assert!(e.variants[0].attrs.is_empty());
+        let fields: Vec<_> = e.variants.iter().map(|v| field_names(&v.fields)).collect();
+        assert_eq!(fields, vec![vec!["a", "x"], vec!["x", "y"]]);
+        assert!(!method_names(&file).contains(&"set_y".to_string()));
+    }
+
+    #[test]
+    fn error_on_skipping_non_optional_field() {
+        let msg = expand_err(
+            quote!({ x: i32 }),
+            quote!(
+                enum E {
+                    #[common_fields(skip(x))]
+                    A,
+                }
+            ),
+        );
+        assert!(
+            msg.contains("not marked with #[variant_optional]"),
+            "{}",
+            msg
+        );
+
+        let msg = expand_err(
+            quote!({ x: i32 }),
+            quote!(
+                enum E {
+                    #[common_fields(skip(z))]
+                    A,
+                }
+            ),
+        );
+        assert!(msg.contains("'z' is not a common field"), "{}", msg);
+    }
+
+    #[test]
+    fn variant_method_names() {
+        let snake = parse_args(quote!(x: i32)).unwrap();
+        let lower = parse_args(quote!(case = "lower", x: i32)).unwrap();
+        let cases = &[
+            ("GitHub", "git_hub", "github"),
+            ("HTTPServer", "http_server", "httpserver"),
+            ("A", "a", "a"),
+            ("Foo_Bar", "foo_bar", "foo_bar"),
+            ("V2Api", "v2_api", "v2api"),
+            ("r#Local", "local", "local"),
+        ];
+        for (variant, want_snake, want_lower) in cases {
+            let ident: Ident = syn::parse_str(variant).unwrap();
+            assert_eq!(variant_method_name(&snake, &ident), *want_snake);
+            assert_eq!(variant_method_name(&lower, &ident), *want_lower);
+        }
+
+        let msg = expand_err(
+            quote!(case = "camel", { x: i32 }),
+            quote!(
+                enum E {
+                    A,
+                }
+            ),
+        );
+        assert!(
+            msg.contains("Unknown value 'camel' for case option"),
+            "{}",
+            msg
+        );
+    }
+
+    #[test]
+    fn snake_case() {
+        assert_eq!(to_snake_case("GitHub"), "git_hub");
+        assert_eq!(to_snake_case("HTTPServer"), "http_server");
+        assert_eq!(to_snake_case("A"), "a");
+        assert_eq!(to_snake_case("lower"), "lower");
+        assert_eq!(to_snake_case("Foo_Bar"), "foo_bar");
+        assert_eq!(to_snake_case("V2Api"), "v2_api");
     }
 }
@@ -1,4 +1,5 @@
 #![cfg(test)]
+#![allow(dead_code)]
 
 // Separated from src/lib.rs since proc macro cannot be used at the same place
 // where it is defined.
@@ -113,6 +114,52 @@ fn derive_enum() {
 
     let s = format!("{:?}", E::B { x: 12 });
     assert_eq!(&s, "B { x: 12 }");
+
+    // Common fields keep the order in the attribute
+    #[common_fields {
+        z: i32,
+        x: i32,
+        y: i32,
+    }]
+    #[derive(Debug)]
+    enum F {
+        A { b: bool, a: bool },
+        B,
+    }
+
+    let s = format!(
+        "{:?}",
+        F::A {
+            a: true,
+            b: false,
+            x: 1,
+            y: 2,
+            z: 3
+        }
+    );
+    assert_eq!(&s, "A { b: false, a: true, z: 3, x: 1, y: 2 }");
+    let s = format!("{:?}", F::B { x: 1, y: 2, z: 3 });
+    assert_eq!(&s, "B { z: 3, x: 1, y: 2 }");
+
+    #[common_fields(prepend, {
+        z: i32,
+        x: i32,
+    })]
+    #[derive(Debug)]
+    enum G {
+        A { b: bool, a: bool },
+    }
+
+    let s = format!(
+        "{:?}",
+        G::A {
+            a: true,
+            b: false,
+            x: 1,
+            z: 3
+        }
+    );
+    assert_eq!(&s, "A { z: 3, x: 1, b: false, a: true }");
 }
 
 #[test]
@@ -124,8 +171,3047 @@ fn avoid_accessor_dead_code_warning() {
     enum E {
         A,
         B,
-    };
+    }
 
     // Use E
-    E::A { x: 42 };
+    let _ = E::A { x: 42 };
+}
+
+#[test]
+fn mutable_accessor() {
+    #[common_fields(accessors = "get,set,mut", {
+        x: i32,
+        s: String,
+    })]
+    enum E {
+        A { b: bool },
+        B,
+    }
+
+    let mut e = E::A {
+        b: true,
+        x: 42,
+        s: "foo".to_string(),
+    };
+    *e.x_mut() += 1;
+    e.s_mut().push_str("bar");
+    assert_eq!(e.x(), &43);
+    assert_eq!(e.s(), "foobar");
+
+    let mut e = E::B {
+        x: 12,
+        s: String::new(),
+    };
+    *e.x_mut() = 21;
+    assert_eq!(e.x(), &21);
+}
+
+#[test]
+fn setter() {
+    #[common_fields(accessors = "get,set,mut", {
+        x: i32,
+        s: String,
+        value: Vec<u8>,
+    })]
+    enum E {
+        A { b: bool },
+        B,
+    }
+
+    let mut e = E::A {
+        b: true,
+        x: 42,
+        s: "foo".to_string(),
+        value: vec![],
+    };
+    e.set_x(10);
+    e.set_s("bar".to_string());
+    e.set_value(vec![1, 2, 3]);
+    assert_eq!(e.x(), &10);
+    assert_eq!(e.s(), "bar");
+    assert_eq!(e.value(), &[1, 2, 3]);
+
+    let mut e = E::B {
+        x: 12,
+        s: String::new(),
+        value: vec![],
+    };
+    e.set_s("baz".to_string());
+    assert_eq!(e.s(), "baz");
+}
+
+#[test]
+fn generic_enum() {
+    #[common_fields(accessors = "get,set,mut", {
+        data: T,
+    })]
+    enum E<T> {
+        A,
+        B { b: bool },
+    }
+
+    let e = E::A { data: 42 };
+    assert_eq!(e.data(), &42);
+
+    let mut e = E::B {
+        b: true,
+        data: "foo".to_string(),
+    };
+    e.data_mut().push_str("bar");
+    assert_eq!(e.data(), "foobar");
+}
+
+#[test]
+fn lifetime_enum() {
+    #[common_fields(accessors = "get,set,mut", {
+        name: &'a str,
+    })]
+    enum E<'a> {
+        A { s: &'a str },
+        B,
+    }
+
+    let owned = "foo".to_string();
+    let e = E::A {
+        s: "bar",
+        name: &owned,
+    };
+    assert_eq!(*e.name(), "foo");
+
+    let mut e = E::B { name: "baz" };
+    e.set_name(&owned);
+    assert_eq!(*e.name(), "foo");
+}
+
+#[test]
+fn where_clause_enum() {
+    #[common_fields {
+        data: T,
+    }]
+    enum E<T>
+    where
+        T: Clone,
+    {
+        A,
+        B { b: bool },
+    }
+
+    let e = E::A {
+        data: vec![1, 2, 3],
+    };
+    assert_eq!(e.data().clone(), vec![1, 2, 3]);
+
+    let e = E::B { b: false, data: 42 };
+    assert_eq!(e.data(), &42);
+}
+
+#[test]
+fn prepend_common_fields() {
+    #[common_fields(prepend, {
+        x: i32,
+        y: i32,
+    })]
+    #[derive(Debug)]
+    enum E {
+        A { b: bool },
+        B,
+    }
+
+    let s = format!(
+        "{:?}",
+        E::A {
+            b: true,
+            x: 1,
+            y: 2
+        }
+    );
+    assert_eq!(&s, "A { x: 1, y: 2, b: true }");
+
+    let s = format!("{:?}", E::B { x: 1, y: 2 });
+    assert_eq!(&s, "B { x: 1, y: 2 }");
+}
+
+#[test]
+fn options_and_fields_in_same_list() {
+    #[common_fields {
+        prepend,
+        x: i32,
+    }]
+    #[derive(Debug)]
+    enum E {
+        A { b: bool },
+    }
+
+    let s = format!("{:?}", E::A { b: true, x: 1 });
+    assert_eq!(&s, "A { x: 1, b: true }");
+}
+
+#[test]
+fn accessor_visibility() {
+    mod m {
+        use diff_enum::common_fields;
+
+        #[common_fields(vis = "pub(crate)", {
+            x: i32,
+        })]
+        pub enum E {
+            A,
+        }
+
+        #[common_fields {
+            x: i32,
+        }]
+        pub(crate) enum F {
+            A,
+        }
+    }
+
+    let e = m::E::A { x: 42 };
+    assert_eq!(e.x(), &42);
+
+    let f = m::F::A { x: 21 };
+    assert_eq!(f.x(), &21);
+}
+
+#[test]
+fn copy_accessor() {
+    #[common_fields(copy, accessors = "get,set,mut", {
+        x: i32,
+        b: bool,
+    })]
+    enum Copied {
+        A,
+        B { s: String },
+    }
+
+    #[common_fields(accessors = "get,set,mut", {
+        x: i32,
+        b: bool,
+    })]
+    enum Borrowed {
+        A,
+        B { s: String },
+    }
+
+    let c = Copied::A { x: 42, b: true };
+    let x: i32 = c.x();
+    assert_eq!(x, 42);
+    assert!(c.b());
+
+    let r = Borrowed::A { x: 42, b: true };
+    let x: &i32 = r.x();
+    assert_eq!(x, &42);
+    assert!(*r.b());
+
+    let mut c = Copied::B {
+        s: String::new(),
+        x: 1,
+        b: false,
+    };
+    *c.x_mut() += 1;
+    assert_eq!(c.x(), 2);
+}
+
+#[test]
+fn into_common_struct() {
+    #[common_fields(common_struct, {
+        user: String,
+        stars: u32,
+    })]
+    enum RemoteRepo {
+        GitHub { language: String },
+        GitLab,
+    }
+
+    let repo = RemoteRepo::GitHub {
+        language: "rust".to_string(),
+        user: "rust-lang".to_string(),
+        stars: 42,
+    };
+    let common: RemoteRepoCommon = repo.into_common();
+    assert_eq!(&common.user, "rust-lang");
+    assert_eq!(common.stars, 42);
+
+    let repo = RemoteRepo::GitLab {
+        user: "gitlab-org".to_string(),
+        stars: 12,
+    };
+    let common = repo.into_common();
+    assert_eq!(&common.user, "gitlab-org");
+    assert_eq!(common.stars, 12);
+}
+
+#[test]
+fn into_common_struct_with_generics() {
+    #[common_fields(common_struct, {
+        name: &'a str,
+        data: T,
+    })]
+    enum E<'a, T, U>
+    where
+        T: Clone,
+        U: Default,
+    {
+        A { u: U },
+        B,
+    }
+
+    let e: E<'_, i32, bool> = E::A {
+        u: true,
+        name: "foo",
+        data: 42,
+    };
+    let common: ECommon<'_, i32> = e.into_common();
+    assert_eq!(common.name, "foo");
+    assert_eq!(common.data, 42);
+
+    let e: E<'_, i32, bool> = E::B {
+        name: "bar",
+        data: 1,
+    };
+    let common: ECommon<'_, i32> = e.into();
+    assert_eq!(common.name, "bar");
+    let e: E<'_, i32, bool> = E::B {
+        name: "baz",
+        data: 2,
+    };
+    assert_eq!(ECommon::from(e).data, 2);
+}
+
+#[test]
+fn construct_with_common_struct() {
+    #[common_fields(common_struct, {
+        user: String,
+        name: String,
+        #[variant_optional]
+        stars: u32,
+    })]
+    #[derive(Debug, PartialEq)]
+    enum RemoteRepo {
+        #[common_fields(skip(stars))]
+        GitHub {
+            language: String,
+            pull_requests: u32,
+        },
+        #[common_fields(skip(stars))]
+        Local,
+        Star,
+    }
+
+    let common = RemoteRepoCommon {
+        user: "rhysd".to_string(),
+        name: "diff-enum".to_string(),
+        stars: None,
+    };
+    let repo = RemoteRepo::git_hub_with_common(common, "rust".to_string(), 3);
+    assert_eq!(
+        repo,
+        RemoteRepo::GitHub {
+            language: "rust".to_string(),
+            pull_requests: 3,
+            user: "rhysd".to_string(),
+            name: "diff-enum".to_string(),
+        }
+    );
+
+    let repo = RemoteRepo::local_with_common(repo.into_common());
+    assert_eq!(repo.user(), "rhysd");
+    assert!(repo.is_local());
+}
+
+#[test]
+fn borrow_common_struct() {
+    #[common_fields(common_struct, {
+        user: String,
+        #[variant_optional]
+        stars: u32,
+    })]
+    enum RemoteRepo {
+        GitHub {
+            language: String,
+        },
+        #[common_fields(skip(stars))]
+        Local,
+    }
+
+    let repo = RemoteRepo::GitHub {
+        language: "rust".to_string(),
+        user: "rust-lang".to_string(),
+        stars: 42,
+    };
+    let common: RemoteRepoCommonRef<'_> = repo.common();
+    assert_eq!(common.user, "rust-lang");
+    assert_eq!(common.stars, Some(&42));
+    // The enum is still available after borrowing the common fields
+    assert!(repo.is_git_hub());
+
+    let repo = RemoteRepo::Local {
+        user: "rhysd".to_string(),
+    };
+    let RemoteRepoCommonRef { user, stars } = repo.common();
+    assert_eq!(user, "rhysd");
+    assert_eq!(stars, None);
+
+    #[common_fields(common_struct, { name: &'a str, data: T })]
+    enum E<'a, T, U> {
+        A { u: U },
+        B,
+    }
+
+    let e: E<'_, Vec<i32>, bool> = E::A {
+        u: true,
+        name: "foo",
+        data: vec![1, 2],
+    };
+    let common: ECommonRef<'_, '_, Vec<i32>> = e.common();
+    assert_eq!(common.name, &"foo");
+    assert_eq!(common.data, &[1, 2]);
+}
+
+#[test]
+fn kind_enum() {
+    #[common_fields(kind, {
+        user: String,
+    })]
+    enum RemoteRepo {
+        GitHub { language: String },
+        GitLab,
+    }
+
+    let repo = RemoteRepo::GitHub {
+        language: "rust".to_string(),
+        user: "rust-lang".to_string(),
+    };
+    assert_eq!(repo.kind(), RemoteRepoKind::GitHub);
+
+    let repo = RemoteRepo::GitLab {
+        user: "gitlab-org".to_string(),
+    };
+    let kind = repo.kind();
+    assert_eq!(kind, RemoteRepoKind::GitLab);
+    assert_ne!(kind, RemoteRepoKind::GitHub);
+    assert_eq!(format!("{:?}", kind), "GitLab");
+}
+
+#[test]
+fn variant_predicates() {
+    #[common_fields {
+        user: String,
+    }]
+    #[allow(non_camel_case_types)]
+    enum RemoteRepo {
+        GitHub { language: String },
+        GitLab,
+        HTTPServer,
+        lower,
+    }
+
+    let repo = RemoteRepo::GitHub {
+        language: "rust".to_string(),
+        user: "rust-lang".to_string(),
+    };
+    assert!(repo.is_git_hub());
+    assert!(!repo.is_git_lab());
+    assert!(!repo.is_http_server());
+    assert!(!repo.is_lower());
+
+    let repo = RemoteRepo::HTTPServer {
+        user: "apache".to_string(),
+    };
+    assert!(repo.is_http_server());
+    assert!(!repo.is_git_hub());
+
+    let repo = RemoteRepo::lower {
+        user: "someone".to_string(),
+    };
+    assert!(repo.is_lower());
+}
+
+#[test]
+fn into_accessor() {
+    #[common_fields(into, {
+        user: String,
+        stars: u32,
+    })]
+    enum RemoteRepo {
+        GitHub { language: String },
+        GitLab,
+    }
+
+    let repo = RemoteRepo::GitHub {
+        language: "rust".to_string(),
+        user: "rust-lang".to_string(),
+        stars: 42,
+    };
+    let user: String = repo.into_user();
+    assert_eq!(&user, "rust-lang");
+
+    let repo = RemoteRepo::GitLab {
+        user: "gitlab-org".to_string(),
+        stars: 12,
+    };
+    assert_eq!(repo.into_stars(), 12);
+}
+
+#[test]
+fn const_generic_enum() {
+    #[common_fields(common_struct, accessors = "get,set,mut", {
+        data: [u8; N],
+    })]
+    enum Buf<const N: usize> {
+        A,
+        B { len: usize },
+    }
+
+    let b: Buf<3> = Buf::A { data: [1, 2, 3] };
+    assert_eq!(b.data(), &[1, 2, 3]);
+
+    let mut b: Buf<2> = Buf::B {
+        len: 1,
+        data: [0, 0],
+    };
+    b.data_mut()[1] = 4;
+    assert_eq!(b.data(), &[0, 4]);
+
+    let c: BufCommon<2> = b.into_common();
+    assert_eq!(c.data, [0, 4]);
+}
+
+#[test]
+fn common_methods() {
+    use diff_enum::common_methods;
+
+    #[common_fields {
+        user: String,
+        name: String,
+    }]
+    #[common_methods {
+        pub fn full_name(&self) -> String {
+            format!("{}/{}", self.user(), self.name())
+        }
+
+        fn name_len(&self) -> usize {
+            self.name().len()
+        }
+    }]
+    enum RemoteRepo {
+        GitHub { language: String },
+        GitLab,
+    }
+
+    let repo = RemoteRepo::GitHub {
+        language: "rust".to_string(),
+        user: "rust-lang".to_string(),
+        name: "rust".to_string(),
+    };
+    assert_eq!(&repo.full_name(), "rust-lang/rust");
+    assert_eq!(repo.name_len(), 4);
+
+    // Order of attributes does not matter
+    #[common_methods {
+        fn full_name(&self) -> String {
+            format!("{}/{}", self.user(), self.name())
+        }
+    }]
+    #[common_fields {
+        user: String,
+        name: String,
+    }]
+    enum E {
+        A,
+    }
+
+    let e = E::A {
+        user: "foo".to_string(),
+        name: "bar".to_string(),
+    };
+    assert_eq!(&e.full_name(), "foo/bar");
+}
+
+#[test]
+fn partial_eq_on_common_fields() {
+    #[common_fields(partial_eq, {
+        user: String,
+        name: String,
+    })]
+    enum RemoteRepo<T> {
+        GitHub { stars: T },
+        GitLab,
+    }
+
+    let a = RemoteRepo::GitHub {
+        stars: 42,
+        user: "rust-lang".to_string(),
+        name: "rust".to_string(),
+    };
+    let b = RemoteRepo::GitHub {
+        stars: 12,
+        user: "rust-lang".to_string(),
+        name: "rust".to_string(),
+    };
+    let c = RemoteRepo::GitLab {
+        user: "rust-lang".to_string(),
+        name: "rust".to_string(),
+    };
+    let d = RemoteRepo::GitLab {
+        user: "rust-lang".to_string(),
+        name: "cargo".to_string(),
+    };
+    assert!(a == b);
+    assert!(a == c);
+    assert!(a != d);
+    assert!(c != d);
+}
+
+#[test]
+fn hash_on_common_fields() {
+    use std::collections::HashSet;
+
+    #[common_fields(partial_eq, hash, {
+        user: String,
+        name: String,
+    })]
+    enum RemoteRepo {
+        GitHub { stars: u32 },
+        GitLab,
+    }
+    impl Eq for RemoteRepo {}
+
+    let mut set = HashSet::new();
+    set.insert(RemoteRepo::GitHub {
+        stars: 42,
+        user: "rust-lang".to_string(),
+        name: "rust".to_string(),
+    });
+    set.insert(RemoteRepo::GitLab {
+        user: "rust-lang".to_string(),
+        name: "rust".to_string(),
+    });
+    set.insert(RemoteRepo::GitLab {
+        user: "rust-lang".to_string(),
+        name: "cargo".to_string(),
+    });
+    assert_eq!(set.len(), 2);
+}
+
+#[test]
+fn generic_enum_in_generic_function() {
+    fn wrap<T: Clone + std::fmt::Debug>(data: T) -> String {
+        #[common_fields {
+            data: U,
+        }]
+        #[derive(Debug, Clone)]
+        enum Local<U> {
+            A,
+            B { b: bool },
+        }
+
+        let e = Local::B { b: true, data };
+        assert!(e.is_b());
+        format!("{:?} {:?}", e.clone(), e.data())
+    }
+
+    assert_eq!(&wrap(42), "B { b: true, data: 42 } 42");
+    assert_eq!(&wrap("foo"), "B { b: true, data: \"foo\" } \"foo\"");
+}
+
+#[test]
+fn allow_override_common_field() {
+    #[common_fields(allow_override, accessors = "get,set,mut", {
+        name: String,
+        stars: u32,
+    })]
+    #[derive(Debug)]
+    enum RemoteRepo {
+        GitHub { name: String, language: String },
+        GitLab,
+    }
+
+    let repo = RemoteRepo::GitHub {
+        name: "rust".to_string(),
+        language: "rust".to_string(),
+        stars: 42,
+    };
+    assert_eq!(repo.name(), "rust");
+    assert_eq!(repo.stars(), &42);
+    assert_eq!(
+        format!("{:?}", repo),
+        "GitHub { name: \"rust\", language: \"rust\", stars: 42 }"
+    );
+
+    let mut repo = RemoteRepo::GitLab {
+        name: "gitlab".to_string(),
+        stars: 12,
+    };
+    repo.set_name("gitlab-ce".to_string());
+    assert_eq!(repo.name(), "gitlab-ce");
+
+    // The overriding field can spell the same type differently
+    type Name = String;
+    #[common_fields(allow_override, {
+        name: ::std::string::String,
+    })]
+    enum Aliased {
+        A { name: Name },
+        B,
+    }
+    assert_eq!(
+        Aliased::A {
+            name: "a".to_string()
+        }
+        .name(),
+        "a"
+    );
+    assert!(Aliased::B {
+        name: "b".to_string()
+    }
+    .is_b());
+}
+
+#[test]
+fn non_exhaustive_enum() {
+    #[common_fields(common_struct, {
+        x: i32,
+    })]
+    #[non_exhaustive]
+    #[derive(Debug)]
+    enum E {
+        A { b: bool },
+        B,
+    }
+
+    let e = E::A { b: true, x: 42 };
+    assert_eq!(e.x(), &42);
+    assert_eq!(format!("{:?}", e), "A { b: true, x: 42 }");
+    assert_eq!(e.into_common().x, 42);
+}
+
+#[test]
+fn variant_optional_common_field() {
+    #[common_fields(into, common_struct, partial_eq, accessors = "get,set,mut", {
+        name: String,
+        #[variant_optional]
+        stars: u32,
+    })]
+    #[derive(Debug)]
+    enum RemoteRepo {
+        GitHub {
+            language: String,
+        },
+        #[common_fields(skip(stars))]
+        Local,
+    }
+
+    let mut repo = RemoteRepo::GitHub {
+        name: "rust".to_string(),
+        language: "rust".to_string(),
+        stars: 42,
+    };
+    assert_eq!(repo.stars(), Some(&42));
+    if let Some(stars) = repo.stars_mut() {
+        *stars += 1;
+    }
+    assert_eq!(repo.stars(), Some(&43));
+
+    let mut local = RemoteRepo::Local {
+        name: "local".to_string(),
+    };
+    assert_eq!(local.stars(), None);
+    assert_eq!(local.stars_mut(), None);
+    assert_eq!(format!("{:?}", local), "Local { name: \"local\" }");
+    assert!(local != repo);
+
+    assert_eq!(repo.into_stars(), Some(43));
+    let common = local.into_common();
+    assert_eq!(common.name, "local");
+    assert_eq!(common.stars, None);
+}
+
+#[test]
+fn accessors_trait() {
+    #[common_fields(trait = "HasRepoInfo", accessors = "get,set,mut", {
+        user: String,
+        name: String,
+    })]
+    enum RemoteRepo {
+        GitHub { pull_requests: u32 },
+        GitLab { merge_requests: u32 },
+    }
+
+    #[common_fields(impl_trait = "HasRepoInfo", accessors = "get,set,mut", {
+        user: String,
+        name: String,
+    })]
+    enum LocalRepo {
+        Git,
+        Svn,
+    }
+
+    #[common_fields(trait = "HasInner", {
+        inner: T,
+    })]
+    enum Wrap<T> {
+        A,
+        B { b: bool },
+    }
+
+    fn full_name<R: HasRepoInfo>(r: &R) -> String {
+        format!("{}/{}", r.user(), r.name())
+    }
+
+    let mut remote = RemoteRepo::GitHub {
+        user: "rust-lang".to_string(),
+        name: "rust".to_string(),
+        pull_requests: 42,
+    };
+    remote.set_name("cargo".to_string());
+    assert_eq!(full_name(&remote), "rust-lang/cargo");
+    assert!(remote.is_git_hub());
+
+    let local = LocalRepo::Svn {
+        user: "me".to_string(),
+        name: "dotfiles".to_string(),
+    };
+    assert_eq!(full_name(&local), "me/dotfiles");
+
+    let w = Wrap::A { inner: 42 };
+    assert_eq!(w.inner(), &42);
+}
+
+#[test]
+fn cfg_common_field() {
+    #[common_fields(
+        common_struct,
+        partial_eq,
+        hash,
+        trait = "HasCfgFields",
+        accessors = "get,set,mut",
+        {
+            x: i32,
+            #[cfg(test)]
+            enabled: u32,
+            #[cfg(not(test))]
+            disabled: u32,
+        }
+    )]
+    #[derive(Debug)]
+    enum E {
+        A { b: bool },
+        B,
+    }
+
+    let mut e = E::A {
+        b: true,
+        x: 1,
+        enabled: 2,
+    };
+    assert_eq!(e.enabled(), &2);
+    e.set_enabled(3);
+    assert_eq!(e, E::B { x: 1, enabled: 3 });
+    assert_ne!(e, E::B { x: 1, enabled: 4 });
+    assert_eq!(format!("{:?}", e), "A { b: true, x: 1, enabled: 3 }");
+
+    let c = e.into_common();
+    assert_eq!((c.x, c.enabled), (1, 3));
+}
+
+#[test]
+fn cfg_variant() {
+    #[common_fields(
+        into,
+        kind,
+        common_struct,
+        common_refs,
+        view,
+        builder,
+        transition,
+        constructor,
+        map,
+        accessors = "get,set,mut",
+        {
+            x: i32,
+            #[variant_optional]
+            y: u32,
+        }
+    )]
+    #[derive(Debug, PartialEq)]
+    enum E {
+        A {
+            a: bool,
+        },
+        // Generated code must not refer to the variant removed by #[cfg]. The type does not exist
+        #[cfg(any())]
+        B {
+            b: NotDefined,
+        },
+        #[cfg(test)]
+        #[common_fields(skip(y))]
+        C,
+    }
+
+    let mut e = E::new_a(true, 1, 2);
+    assert!(e.is_a());
+    e.set_x(3);
+    assert_eq!(e.common_refs(), (&3, Some(&2)));
+    assert_eq!(e.kind(), EKind::A);
+    assert_eq!(e.variant_name(), "A");
+    assert!(e.as_a().is_some());
+    let e = e.with_c().map_x(|x| x + 1);
+    assert_eq!(e, E::C { x: 4 });
+    assert_eq!(e.kind(), EKind::C);
+    assert_eq!(e.y(), None);
+    assert_eq!(E::c_builder().x(5).build(), Ok(E::C { x: 5 }));
+    assert_eq!(e.into_common().x, 4);
+}
+
+#[test]
+fn variant_builder() {
+    #[common_fields(builder, {
+        user: String,
+        name: String,
+    })]
+    #[derive(Debug, PartialEq)]
+    enum RemoteRepo {
+        GitHub { language: String },
+        GitLab,
+    }
+
+    let repo = RemoteRepo::git_hub_builder()
+        .user("rust-lang".to_string())
+        .name("rust".to_string())
+        .language("rust".to_string())
+        .build()
+        .unwrap();
+    assert_eq!(
+        repo,
+        RemoteRepo::GitHub {
+            user: "rust-lang".to_string(),
+            name: "rust".to_string(),
+            language: "rust".to_string(),
+        }
+    );
+
+    let err = RemoteRepo::git_lab_builder()
+        .user("gitlab-org".to_string())
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        err,
+        "Field 'name' is not set to builder of RemoteRepo::GitLab"
+    );
+
+    #[common_fields(builder, { inner: T })]
+    enum Wrap<'a, T> {
+        A { r: &'a str },
+    }
+    let w = Wrap::a_builder().r("hello").inner(42).build().unwrap();
+    assert_eq!(w.inner(), &42);
+}
+
+#[test]
+fn tuple_variants() {
+    #[common_fields(tuple, into, common_struct, accessors = "get,set,mut", {
+        x: i32,
+        #[variant_optional]
+        y: u32,
+    })]
+    #[derive(Debug, PartialEq)]
+    enum E {
+        A(bool, &'static str),
+        #[common_fields(skip(y))]
+        B(bool),
+        C,
+        D {
+            d: bool,
+        },
+    }
+
+    let mut a = E::A(true, "a", 1, 2);
+    assert_eq!(a.x(), &1);
+    assert_eq!(a.y(), Some(&2));
+    a.set_x(10);
+    *a.y_mut().unwrap() = 20;
+    assert_eq!(a, E::A(true, "a", 10, 20));
+
+    let b = E::B(false, 3);
+    assert_eq!(b.x(), &3);
+    assert_eq!(b.y(), None);
+    assert_eq!(b.into_x(), 3);
+
+    let c = E::C(4, 5);
+    assert!(c.is_c());
+    let common = c.into_common();
+    assert_eq!((common.x, common.y), (4, Some(5)));
+
+    let d = E::D {
+        d: true,
+        x: 6,
+        y: 7,
+    };
+    assert_eq!(d.y(), Some(&7));
+
+    #[common_fields(tuple, prepend, accessors = "get,set,mut", { x: i32 })]
+    #[derive(Debug, PartialEq)]
+    enum P {
+        A(bool),
+        B,
+    }
+    let mut p = P::A(1, true);
+    p.set_x(2);
+    assert_eq!(p, P::A(2, true));
+    assert_eq!(P::B(3).x(), &3);
+}
+
+#[test]
+fn self_type_in_common_fields() {
+    #[common_fields(common_struct, partial_eq, builder, {
+        value: i32,
+        next: Option<Box<Self>>,
+    })]
+    #[derive(Debug)]
+    enum List<T> {
+        Node { data: T },
+        Tail,
+    }
+
+    let list = List::Node {
+        data: "head",
+        value: 1,
+        next: Some(Box::new(List::Tail {
+            value: 2,
+            next: None,
+        })),
+    };
+    let next = list.next().as_ref().unwrap();
+    assert_eq!(next.value(), &2);
+    assert!(next.is_tail());
+
+    let built = List::node_builder()
+        .data("head")
+        .value(1)
+        .next(Some(Box::new(
+            List::tail_builder().value(2).next(None).build().unwrap(),
+        )))
+        .build()
+        .unwrap();
+    assert_eq!(built, list);
+
+    let common: ListCommon<&str> = list.into_common();
+    assert_eq!(common.value, 1);
+    assert_eq!(common.next.unwrap().value(), &2);
+}
+
+#[test]
+fn common_match_macro() {
+    use diff_enum::common_match;
+
+    #[common_fields(copy, {
+        stars: u32,
+        issues: u32,
+    })]
+    enum RemoteRepo {
+        GitHub { pull_requests: u32 },
+        GitLab { merge_requests: u32 },
+    }
+
+    let repos = [
+        RemoteRepo::GitHub {
+            pull_requests: 1,
+            stars: 10,
+            issues: 2,
+        },
+        RemoteRepo::GitLab {
+            merge_requests: 3,
+            stars: 5,
+            issues: 4,
+        },
+    ];
+    let scores: Vec<_> = repos
+        .iter()
+        .map(|r| common_match!(r, { stars, issues } => stars * 10 + issues))
+        .collect();
+    assert_eq!(scores, vec![102, 54]);
+
+    let total = common_match!(repos[0], { stars, } => {
+        let s: u32 = *stars;
+        s + 1
+    });
+    assert_eq!(total, 11);
+}
+
+#[test]
+#[deny(unused_braces)]
+fn common_match_without_getters() {
+    use diff_enum::common_match;
+
+    mod repo {
+        use diff_enum::common_fields;
+
+        // The trait is not imported where common_match! is used
+        #[common_fields(trait = "HasStars", {
+            #[accessor(name = "star_count")]
+            stars: u32,
+            #[variant_optional]
+            issues: u32,
+        })]
+        pub enum RemoteRepo {
+            GitHub,
+            #[common_fields(skip(issues))]
+            Local,
+        }
+    }
+
+    #[common_fields(accessors = "set", { x: i32, r#type: &'static str })]
+    enum E {
+        A { a: bool },
+        B,
+    }
+
+    let r = repo::RemoteRepo::GitHub {
+        stars: 3,
+        issues: 1,
+    };
+    assert_eq!(
+        common_match!(r, { stars, issues } => { *stars + *issues.unwrap() }),
+        4
+    );
+    let r = repo::RemoteRepo::Local { stars: 5 };
+    assert_eq!(common_match!(r, { issues } => issues), None);
+
+    let e = E::A {
+        a: true,
+        x: 1,
+        r#type: "a",
+    };
+    assert_eq!(common_match!(e, { x, r#type } => (*x, *r#type)), (1, "a"));
+}
+
+#[test]
+#[deny(warnings)]
+fn non_exhaustive_enum_without_warnings() {
+    #[common_fields(into, kind, common_struct, partial_eq, hash, builder, {
+        x: i32,
+        #[variant_optional]
+        y: u32,
+    })]
+    #[non_exhaustive]
+    #[derive(Debug)]
+    enum E {
+        A {
+            b: bool,
+        },
+        #[common_fields(skip(y))]
+        B,
+    }
+
+    let e = E::a_builder().b(true).x(1).y(2).build().unwrap();
+    assert_eq!(e.y(), Some(&2));
+    assert_eq!(e.kind(), EKind::A);
+    assert!(e.is_a());
+    assert!(e != E::B { x: 2 });
+    assert_eq!(e.into_x(), 1);
+}
+
+#[test]
+fn accessor_name_override() {
+    #[common_fields(into, partial_eq, accessors = "get,set,mut", {
+        #[accessor(name = "repo_user")]
+        user: String,
+        name: String,
+    })]
+    #[derive(Debug)]
+    enum RemoteRepo {
+        GitHub,
+        GitLab,
+    }
+
+    impl RemoteRepo {
+        // Not conflict with the accessor
+        fn user(&self) -> String {
+            format!("@{}", self.repo_user())
+        }
+    }
+
+    let mut repo = RemoteRepo::GitHub {
+        user: "rust-lang".to_string(),
+        name: "rust".to_string(),
+    };
+    assert_eq!(repo.repo_user(), "rust-lang");
+    assert_eq!(repo.user(), "@rust-lang");
+    assert_eq!(repo.name(), "rust");
+    repo.repo_user_mut().push_str("-nursery");
+    repo.set_repo_user("rust-lang".to_string());
+    assert_eq!(
+        repo,
+        RemoteRepo::GitLab {
+            user: "rust-lang".to_string(),
+            name: "rust".to_string(),
+        }
+    );
+    assert_eq!(repo.into_repo_user(), "rust-lang");
+}
+
+#[test]
+fn as_ref_common_field() {
+    #[common_fields(into, {
+        #[as_ref(str, [u8])]
+        name: String,
+        #[as_ref]
+        path: std::path::PathBuf,
+        stars: u32,
+    })]
+    enum RemoteRepo {
+        GitHub,
+        GitLab { merge_requests: u32 },
+    }
+
+    fn len<S: AsRef<str>>(s: &S) -> usize {
+        s.as_ref().len()
+    }
+
+    let repo = RemoteRepo::GitLab {
+        name: "rust".to_string(),
+        path: "/path/to/rust".into(),
+        stars: 10,
+        merge_requests: 0,
+    };
+    let s: &str = repo.as_ref();
+    assert_eq!(s, "rust");
+    let b: &[u8] = repo.as_ref();
+    assert_eq!(b, b"rust");
+    let p: &std::path::PathBuf = repo.as_ref();
+    assert_eq!(p.to_str(), Some("/path/to/rust"));
+    assert_eq!(len(&repo), 4);
+    assert_eq!(repo.into_stars(), 10);
+}
+
+#[test]
+fn discriminant_with_repr() {
+    #[common_fields { x: i32 }]
+    #[repr(u8)]
+    enum E {
+        A = 1,
+        B { b: bool } = 2,
+    }
+
+    let e = E::A { x: 42 };
+    assert_eq!(e.x(), &42);
+    assert!(E::B { b: true, x: 1 }.is_b());
+
+    // C-like discriminant is available via kind enum
+    #[common_fields(kind(repr(u8)), { x: i32 })]
+    enum F {
+        A,
+        B { b: bool },
+    }
+    assert_eq!(F::A { x: 1 }.kind() as u8, 0);
+    assert_eq!(F::B { b: true, x: 1 }.kind() as u8, 1);
+    assert_eq!(std::mem::size_of::<FKind>(), 1);
+}
+
+#[test]
+fn inline_option() {
+    #[common_fields(inline = "always", { x: i32 })]
+    enum A {
+        V,
+    }
+    #[common_fields(inline = "never", { x: i32 })]
+    enum B {
+        V,
+    }
+    #[common_fields(inline = "none", { x: i32 })]
+    enum C {
+        V,
+    }
+
+    assert_eq!(A::V { x: 1 }.x(), &1);
+    assert_eq!(B::V { x: 2 }.x(), &2);
+    assert_eq!(C::V { x: 3 }.x(), &3);
+}
+
+#[test]
+fn default_variant() {
+    #[common_fields(default = "GitLab", {
+        user: String,
+        stars: u32,
+    })]
+    #[derive(Debug, PartialEq)]
+    enum RemoteRepo {
+        GitHub { language: String },
+        GitLab { merge_requests: Option<u32> },
+    }
+
+    assert_eq!(
+        RemoteRepo::default(),
+        RemoteRepo::GitLab {
+            user: String::new(),
+            stars: 0,
+            merge_requests: None,
+        }
+    );
+
+    #[common_fields(tuple, default = "A", { x: T })]
+    #[derive(Debug, PartialEq)]
+    enum E<T> {
+        A(bool),
+        B,
+    }
+    assert_eq!(E::<i32>::default(), E::A(false, 0));
+}
+
+#[test]
+fn stacked_common_fields() {
+    #[common_fields {
+        user: String,
+        name: String,
+    }]
+    #[derive(Debug)]
+    #[diff_enum::common_fields(into, {
+        stars: u32,
+        issues: u32,
+    })]
+    enum RemoteRepo {
+        GitHub { pull_requests: u32 },
+        GitLab,
+    }
+
+    let repo = RemoteRepo::GitHub {
+        pull_requests: 1,
+        user: "rust-lang".to_string(),
+        name: "rust".to_string(),
+        stars: 10,
+        issues: 2,
+    };
+    assert_eq!(repo.user(), "rust-lang");
+    assert_eq!(repo.stars(), &10);
+    assert_eq!(
+        format!("{:?}", repo),
+        "GitHub { pull_requests: 1, user: \"rust-lang\", name: \"rust\", stars: 10, issues: 2 }"
+    );
+    assert_eq!(repo.into_name(), "rust");
+}
+
+#[test]
+fn kind_enum_options() {
+    mod m {
+        use diff_enum::common_fields;
+
+        #[common_fields(kind(vis = "pub", derive(Hash, PartialOrd, Ord)), {
+            x: i32,
+        })]
+        pub(crate) enum E {
+            A,
+            B { b: bool },
+        }
+    }
+
+    use std::collections::HashSet;
+
+    let kinds: HashSet<m::EKind> = [m::E::A { x: 1 }, m::E::B { b: true, x: 2 }]
+        .iter()
+        .map(|e| e.kind())
+        .collect();
+    assert_eq!(kinds.len(), 2);
+    assert!(m::EKind::A < m::EKind::B);
+}
+
+#[test]
+fn no_accessors() {
+    #[common_fields(no_accessors, {
+        x: i32,
+    })]
+    enum E {
+        A { a: bool },
+        B,
+    }
+
+    impl E {
+        // Would conflict with the accessor when it is generated
+        fn x(&self) -> i32 {
+            match self {
+                E::A { x, .. } | E::B { x } => *x,
+            }
+        }
+    }
+
+    let e = E::A { a: true, x: 1 };
+    assert_eq!(e.x(), 1);
+    assert_eq!(E::B { x: 2 }.x(), 2);
+}
+
+#[test]
+fn pin_projection_accessor() {
+    use std::marker::PhantomPinned;
+    use std::pin::Pin;
+
+    struct Counter {
+        count: u32,
+        _pinned: PhantomPinned,
+    }
+
+    impl Counter {
+        fn incr(self: Pin<&mut Self>) {
+            // Safe since `count` is not pinned
+            unsafe { self.get_unchecked_mut().count += 1 }
+        }
+    }
+
+    #[common_fields {
+        #[pin]
+        counter: Counter,
+        name: &'static str,
+    }]
+    enum E {
+        A,
+        B { b: bool },
+    }
+
+    let mut e = Box::pin(E::B {
+        b: true,
+        name: "foo",
+        counter: Counter {
+            count: 0,
+            _pinned: PhantomPinned,
+        },
+    });
+    e.as_mut().counter_pin_mut().incr();
+    e.as_mut().counter_pin_mut().incr();
+    assert_eq!(e.counter().count, 2);
+    assert_eq!(e.name(), &"foo");
+
+    // The enum is still `Unpin` when its pinned fields are `Unpin`
+    #[common_fields {
+        #[pin]
+        x: i32,
+    }]
+    enum Unpinned<T> {
+        A { t: T },
+    }
+    fn assert_unpin<T: Unpin>(_: &T) {}
+    let mut u = Unpinned::A { t: 1, x: 0 };
+    assert_unpin(&u);
+    *Pin::new(&mut u).x_pin_mut() += 1;
+    assert_eq!(u.x(), &1);
+}
+
+#[test]
+fn where_clause_in_attribute() {
+    use std::borrow::Cow;
+
+    #[common_fields(common_struct, accessors = "get,set,mut", where T: ToOwned + ?Sized, {
+        name: Cow<'a, T>,
+    })]
+    enum E<'a, T> {
+        A,
+        B { b: bool },
+    }
+
+    let e: E<'_, str> = E::A {
+        name: Cow::Borrowed("foo"),
+    };
+    assert_eq!(e.name(), "foo");
+    let mut e: E<'_, [u8]> = E::B {
+        b: true,
+        name: Cow::Borrowed(b"foo"),
+    };
+    e.name_mut().to_mut().push(b'!');
+    assert_eq!(e.into_common().name.as_ref(), b"foo!");
+
+    #[common_fields(x: T, where T: Clone)]
+    enum F<T> {
+        A,
+    }
+    assert_eq!(F::A { x: 1 }.x(), &1);
+}
+
+#[test]
+fn variant_views() {
+    #[common_fields(view, {
+        user: String,
+    })]
+    enum RemoteRepo<T>
+    where
+        T: Clone,
+    {
+        GitHub {
+            language: String,
+            pull_requests: u32,
+        },
+        GitLab {
+            data: T,
+        },
+        Local,
+    }
+
+    let repo: RemoteRepo<bool> = RemoteRepo::GitHub {
+        language: "rust".to_string(),
+        pull_requests: 1,
+        user: "rust-lang".to_string(),
+    };
+    let view = repo.as_git_hub().unwrap();
+    assert_eq!(view.language, "rust");
+    assert_eq!(view.pull_requests, &1);
+    assert!(repo.as_git_lab().is_none());
+
+    let repo = RemoteRepo::GitLab {
+        data: 42,
+        user: "gitlab-org".to_string(),
+    };
+    let RemoteRepoGitLabRef { data } = repo.as_git_lab().unwrap();
+    assert_eq!(data, &42);
+
+    #[common_fields(view, tuple, prepend, { x: i32 })]
+    enum E {
+        A(bool, &'static str),
+        B,
+    }
+    let e = E::A(1, true, "foo");
+    let EARef(b, s) = e.as_a().unwrap();
+    assert_eq!((b, s), (&true, &"foo"));
+    assert!(E::B(2).as_a().is_none());
+}
+
+#[test]
+fn shadowed_prelude_names() {
+    mod m {
+        #![allow(non_camel_case_types)]
+        use diff_enum::common_fields;
+
+        // Shadow prelude items to check generated code does not depend on them
+        pub struct Option;
+        pub struct Some;
+        pub struct None;
+        pub struct Result;
+        pub struct Ok;
+        pub struct Err;
+        pub struct Default;
+        pub struct PartialEq;
+        pub struct Box;
+        pub struct From;
+        pub struct AsRef;
+        pub struct bool;
+        pub struct str;
+
+        #[common_fields(
+            into,
+            common_struct,
+            kind,
+            partial_eq,
+            hash,
+            builder,
+            view,
+            default = "A",
+            trait = "HasX",
+            {
+                #[as_ref]
+                x: i32,
+                #[variant_optional]
+                y: u32,
+            }
+        )]
+        pub enum E {
+            A {
+                a: i64,
+            },
+            #[common_fields(skip(y))]
+            B,
+        }
+
+        pub fn check() {
+            let e = E::a_builder().a(1).x(2).y(3).build().unwrap();
+            assert!(e.is_a());
+            assert_eq!(e.y(), ::core::option::Option::Some(&3));
+            assert!(e != E::B { x: 2 });
+            assert_eq!(e.as_a().unwrap().a, &1);
+            let _ = E::default();
+            let _: ECommon = e.into();
+        }
+    }
+
+    m::check();
+}
+
+#[test]
+fn const_accessors() {
+    #[common_fields(const, copy, {
+        x: i32,
+        #[variant_optional]
+        y: u8,
+    })]
+    enum E {
+        A {
+            a: bool,
+        },
+        #[common_fields(skip(y))]
+        B,
+    }
+    #[common_fields(const, { name: &'static str })]
+    enum R {
+        V,
+    }
+
+    const A: E = E::A {
+        a: true,
+        x: 1,
+        y: 2,
+    };
+    const X: i32 = A.x();
+    const Y: Option<u8> = A.y();
+    const IS_A: bool = A.is_a();
+    const IS_B: bool = A.is_b();
+    const V: R = R::V { name: "foo" };
+    const NAME: &&str = V.name();
+    const B_Y: Option<u8> = E::B { x: 3 }.y();
+
+    assert_eq!(X, 1);
+    assert_eq!(Y, Some(2));
+    assert_eq!((IS_A, IS_B), (true, false));
+    assert_eq!(*NAME, "foo");
+    assert_eq!(B_Y, None);
+}
+
+#[test]
+#[deny(deprecated)]
+fn deprecated_unit_variant() {
+    #[common_fields(into, kind, common_struct, partial_eq, hash, builder, view, default = "Old", {
+        x: i32,
+    })]
+    #[derive(Debug)]
+    enum E {
+        A {
+            a: bool,
+        },
+        #[deprecated]
+        Old,
+    }
+
+    // Attributes on the unit variant are kept after common fields are injected
+    #[allow(deprecated)]
+    let e = E::Old { x: 3 };
+    assert_eq!(e.x(), &3);
+    assert!(e.is_old());
+    assert_eq!(e.kind(), EKind::Old);
+    assert!(E::default().is_old());
+    assert!(e == E::old_builder().x(3).build().unwrap());
+    assert_eq!(E::old_builder().x(3).build().unwrap().into_x(), 3);
+}
+
+#[test]
+fn variant_transition() {
+    #[common_fields(transition, tuple, {
+        user: String,
+        #[variant_optional]
+        stars: u32,
+    })]
+    #[derive(Debug, PartialEq)]
+    enum RemoteRepo {
+        GitHub {
+            issues: u32,
+        },
+        #[common_fields(skip(stars))]
+        GitLab {
+            merge_requests: u32,
+        },
+        Local(bool),
+    }
+
+    let repo = RemoteRepo::GitHub {
+        user: "rhysd".to_string(),
+        stars: 10,
+        issues: 3,
+    };
+    // Optional common field is dropped when the target variant skips it
+    let repo = repo.with_git_lab(5);
+    assert_eq!(
+        repo,
+        RemoteRepo::GitLab {
+            user: "rhysd".to_string(),
+            merge_requests: 5,
+        }
+    );
+    assert_eq!(repo.stars(), None);
+
+    // Tuple variant can be a source of transition
+    let repo = RemoteRepo::Local(true, "foo".to_string(), 1);
+    assert_eq!(repo.with_git_lab(1).user(), "foo");
+
+    #[common_fields(transition, { name: String })]
+    #[derive(Debug, PartialEq)]
+    enum E {
+        A { a: bool },
+        B,
+    }
+
+    let e = E::A {
+        a: true,
+        name: "foo".to_string(),
+    };
+    let e = e.with_b();
+    assert_eq!(
+        e,
+        E::B {
+            name: "foo".to_string()
+        }
+    );
+    assert_eq!(e.with_a(false).name(), "foo");
+}
+
+#[test]
+fn deref_accessor() {
+    #[common_fields(partial_eq, hash, accessors = "get,set,mut", {
+        #[deref]
+        user: String,
+        #[deref]
+        bytes: Vec<u8>,
+        #[deref]
+        value: Box<T>,
+        #[deref]
+        #[variant_optional]
+        name: String,
+    })]
+    enum E<T: std::hash::Hash + PartialEq> {
+        A,
+        #[common_fields(skip(name))]
+        B,
+    }
+
+    let mut e = E::A {
+        user: "rhysd".to_string(),
+        bytes: vec![1, 2],
+        value: Box::new(42),
+        name: "foo".to_string(),
+    };
+    let user: &str = e.user();
+    assert_eq!(user, "rhysd");
+    let bytes: &[u8] = e.bytes();
+    assert_eq!(bytes, &[1, 2]);
+    let value: &i32 = e.value();
+    assert_eq!(*value, 42);
+    assert_eq!(e.name(), Some("foo"));
+    // Other accessors are not affected
+    e.user_mut().push('!');
+    assert_eq!(e.user(), "rhysd!");
+
+    let e = E::B {
+        user: "rhysd".to_string(),
+        bytes: vec![],
+        value: Box::new(0),
+    };
+    assert_eq!(e.name(), None);
+}
+
+#[test]
+fn serde_flatten() {
+    #[common_fields(serde_flatten, tuple, {
+        user: String,
+        #[variant_optional]
+        stars: u32,
+    })]
+    #[derive(Debug, PartialEq)]
+    enum RemoteRepo {
+        GitHub {
+            issues: u32,
+        },
+        #[common_fields(skip(stars))]
+        GitLab,
+        Local(bool),
+    }
+
+    let repo = RemoteRepo::GitHub {
+        user: "rhysd".to_string(),
+        stars: 10,
+        issues: 3,
+    };
+    let json = serde_json::to_value(&repo).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({"user": "rhysd", "stars": 10, "GitHub": {"issues": 3}})
+    );
+    let decoded: RemoteRepo = serde_json::from_value(json).unwrap();
+    assert_eq!(decoded, repo);
+
+    let repo = RemoteRepo::GitLab("rhysd".to_string());
+    let json = serde_json::to_value(&repo).unwrap();
+    assert_eq!(json, serde_json::json!({"user": "rhysd", "GitLab": {}}));
+    let decoded: RemoteRepo = serde_json::from_value(json).unwrap();
+    assert_eq!(decoded, repo);
+
+    let repo = RemoteRepo::Local(true, "rhysd".to_string(), 1);
+    let json = serde_json::to_string(&repo).unwrap();
+    let decoded: RemoteRepo = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded, repo);
+
+    // Optional common field is required by the variant which has it
+    let err = serde_json::from_str::<RemoteRepo>(r#"{"user": "rhysd", "GitHub": {"issues": 3}}"#)
+        .unwrap_err();
+    assert!(err.to_string().contains("missing field `stars`"), "{}", err);
+}
+
+#[test]
+fn serde_flatten_generic() {
+    #[common_fields(serde_flatten, { id: T })]
+    #[derive(Debug, PartialEq)]
+    enum E<T> {
+        A { a: Vec<T> },
+        B,
+    }
+
+    let e = E::A {
+        id: 1u8,
+        a: vec![2],
+    };
+    let json = serde_json::to_string(&e).unwrap();
+    assert_eq!(json, r#"{"id":1,"A":{"a":[2]}}"#);
+    assert_eq!(serde_json::from_str::<E<u8>>(&json).unwrap(), e);
+    assert_eq!(
+        serde_json::from_str::<E<u8>>(r#"{"id":1,"B":{}}"#).unwrap(),
+        E::B { id: 1 }
+    );
+}
+
+#[test]
+fn shared_common_fields() {
+    use std::sync::Arc;
+
+    #[derive(Debug, PartialEq)]
+    struct Heavy {
+        data: Vec<u8>,
+    }
+
+    #[common_fields(shared, partial_eq, accessors = "get,set,mut", {
+        heavy: Heavy,
+        name: String,
+    })]
+    #[derive(Debug, Clone)]
+    enum E {
+        A { a: bool },
+        B,
+    }
+
+    let heavy = Arc::new(Heavy { data: vec![1, 2] });
+    let mut e = E::A {
+        a: true,
+        heavy: heavy.clone(),
+        name: Arc::new("foo".to_string()),
+    };
+    let heavy_ref: &Heavy = e.heavy();
+    assert_eq!(heavy_ref.data, vec![1, 2]);
+    assert_eq!(e.name(), "foo");
+
+    // Cloning only increments the reference count
+    let cloned = e.clone();
+    assert_eq!(Arc::strong_count(&heavy), 3);
+    assert!(std::ptr::eq(cloned.heavy(), e.heavy()));
+    assert!(cloned == e);
+
+    // Mutable accessor returns the Arc so that it can be updated by clone-on-write
+    Arc::make_mut(e.name_mut()).push('!');
+    assert_eq!(e.name(), "foo!");
+    assert_eq!(cloned.name(), "foo");
+    e.set_heavy(Arc::new(Heavy { data: vec![] }));
+    assert_eq!(Arc::strong_count(&heavy), 2);
+}
+
+#[test]
+fn shared_common_fields_with_defaults() {
+    use std::sync::Arc;
+
+    #[common_fields(shared, builder, default = "A", {
+        x: i32 = 3,
+        name: String = "foo".to_string(),
+    })]
+    #[derive(Debug, PartialEq)]
+    enum E {
+        A,
+        B { b: bool },
+    }
+
+    let e = E::b_builder().b(true).build().unwrap();
+    assert_eq!(e.x(), &3);
+    assert_eq!(e.name(), "foo");
+    let e = E::default();
+    assert_eq!(
+        e,
+        E::A {
+            x: Arc::new(3),
+            name: Arc::new("foo".to_string()),
+        }
+    );
+}
+
+#[test]
+fn shared_common_fields_with_copy() {
+    use std::sync::Arc;
+
+    #[common_fields(shared, copy, {
+        x: i32,
+        #[variant_optional]
+        y: u32,
+    })]
+    enum E {
+        A,
+        #[common_fields(skip(y))]
+        B,
+    }
+
+    let e = E::A {
+        x: Arc::new(1),
+        y: Arc::new(2),
+    };
+    let x: i32 = e.x();
+    assert_eq!(x, 1);
+    assert_eq!(e.y(), Some(2));
+    assert_eq!(E::B { x: Arc::new(3) }.y(), None);
+}
+
+#[test]
+fn common_field_default_values() {
+    #[common_fields(builder, default = "GitHub", {
+        user: String,
+        stars: u32 = 0,
+        name: String = "diff-enum".to_string(),
+    })]
+    #[derive(Debug, PartialEq)]
+    enum RemoteRepo {
+        GitHub { issues: u32 },
+        GitLab,
+    }
+
+    // Fields with default values can be omitted
+    let repo = RemoteRepo::git_lab_builder()
+        .user("rhysd".to_string())
+        .build()
+        .unwrap();
+    assert_eq!(
+        repo,
+        RemoteRepo::GitLab {
+            user: "rhysd".to_string(),
+            stars: 0,
+            name: "diff-enum".to_string(),
+        }
+    );
+
+    // Explicitly set values are preferred
+    let repo = RemoteRepo::git_lab_builder()
+        .user("rhysd".to_string())
+        .stars(10)
+        .build()
+        .unwrap();
+    assert_eq!(repo.stars(), &10);
+
+    // Fields without default values are still required
+    let err = RemoteRepo::git_hub_builder().issues(1).build().unwrap_err();
+    assert_eq!(
+        err,
+        "Field 'user' is not set to builder of RemoteRepo::GitHub"
+    );
+
+    assert_eq!(
+        RemoteRepo::default(),
+        RemoteRepo::GitHub {
+            user: String::new(),
+            stars: 0,
+            name: "diff-enum".to_string(),
+            issues: 0,
+        }
+    );
+}
+
+#[test]
+fn common_refs_tuple() {
+    #[common_fields(common_refs, {
+        user: String,
+        #[variant_optional]
+        stars: u32,
+    })]
+    enum RemoteRepo {
+        GitHub {
+            issues: u32,
+        },
+        #[common_fields(skip(stars))]
+        GitLab,
+    }
+
+    let repo = RemoteRepo::GitHub {
+        user: "rhysd".to_string(),
+        stars: 10,
+        issues: 3,
+    };
+    let (user, stars) = repo.common_refs();
+    assert_eq!(user, "rhysd");
+    assert_eq!(stars, Some(&10));
+
+    let repo = RemoteRepo::GitLab {
+        user: "foo".to_string(),
+    };
+    let (user, stars) = repo.common_refs();
+    assert_eq!(user, "foo");
+    assert_eq!(stars, None);
+
+    #[common_fields(common_refs, { x: i32 })]
+    enum E {
+        A,
+    }
+    let (x,) = E::A { x: 1 }.common_refs();
+    assert_eq!(x, &1);
+}
+
+#[test]
+fn variant_skipping_all_common_fields() {
+    #[common_fields(kind, accessors = "get,set,mut", {
+        #[variant_optional]
+        user: String,
+        #[variant_optional]
+        stars: u32,
+    })]
+    enum RemoteRepo {
+        GitHub {
+            issues: u32,
+        },
+        #[common_fields(skip)]
+        Invalid,
+        #[common_fields(skip)]
+        Poisoned {
+            reason: String,
+        },
+    }
+
+    let repo = RemoteRepo::GitHub {
+        user: "rhysd".to_string(),
+        stars: 10,
+        issues: 3,
+    };
+    assert_eq!(repo.user().map(String::as_str), Some("rhysd"));
+    assert_eq!(repo.stars(), Some(&10));
+
+    // Skipped variants are left untouched
+    let repo = RemoteRepo::Invalid;
+    assert_eq!(repo.user(), None);
+    assert_eq!(repo.stars(), None);
+    assert_eq!(repo.kind(), RemoteRepoKind::Invalid);
+
+    let mut repo = RemoteRepo::Poisoned {
+        reason: "broken".to_string(),
+    };
+    assert_eq!(repo.user_mut(), None);
+    assert!(repo.is_poisoned());
+}
+
+#[test]
+#[deny(warnings)]
+fn must_use_accessors() {
+    #[common_fields(must_use, accessors = "get,set,mut", { x: i32 })]
+    enum E {
+        A,
+    }
+    #[common_fields(must_use, trait = "MustUseAccessors", accessors = "get,set,mut", { y: i32 })]
+    enum F {
+        A,
+    }
+
+    let mut e = E::A { x: 1 };
+    assert_eq!(e.x(), &1);
+    // Other accessors are not affected
+    e.set_x(2);
+    *e.x_mut() = 3;
+    assert_eq!(F::A { y: 1 }.y(), &1);
+}
+
+#[test]
+fn raw_identifier_common_field() {
+    #[common_fields(into, builder, common_struct, accessors = "get,set,mut", {
+        r#type: String,
+        #[variant_optional]
+        r#match: u32,
+    })]
+    enum E {
+        A {
+            r#ref: bool,
+        },
+        #[common_fields(skip(r#match))]
+        B,
+    }
+
+    let mut e = E::A {
+        r#type: "foo".to_string(),
+        r#match: 1,
+        r#ref: true,
+    };
+    assert_eq!(e.r#type(), "foo");
+    assert_eq!(e.r#match(), Some(&1));
+    e.type_mut().push('!');
+    e.set_type("bar".to_string());
+    *e.match_mut().unwrap() = 2;
+    assert_eq!(e.r#match(), Some(&2));
+    assert_eq!(e.into_type(), "bar");
+
+    let e = E::b_builder().r#type("baz".to_string()).build().unwrap();
+    assert_eq!(e.r#match(), None);
+    let c = e.into_common();
+    assert_eq!(c.r#type, "baz");
+}
+
+#[test]
+fn derive_below_attribute() {
+    // #[derive(...)] must be put below #[common_fields] to see the injected fields. Derives above it
+    // are expanded with the enum before injecting common fields.
+    #[common_fields { x: i32 }]
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    #[derive(Clone)]
+    enum E {
+        A { a: bool },
+        B,
+    }
+
+    let e = E::A { a: true, x: 1 };
+    assert_eq!(format!("{:?}", e.clone()), "A { a: true, x: 1 }");
+    assert_eq!(format!("{:?}", E::B { x: 2 }), "B { x: 2 }");
+}
+
+#[test]
+fn common_field_names() {
+    #[common_fields {
+        user: String,
+        #[variant_optional]
+        stars: u32,
+        r#type: bool,
+        #[cfg(any())]
+        removed: i32,
+    }]
+    #[allow(dead_code)]
+    enum E {
+        A,
+        #[common_fields(skip(stars))]
+        B {
+            b: i32,
+        },
+    }
+
+    assert_eq!(E::common_field_names(), &["user", "stars", "type"]);
+}
+
+#[test]
+fn cow_accessors() {
+    use std::borrow::Cow;
+
+    #[common_fields {
+        #[cow]
+        user: String,
+        #[cow]
+        #[variant_optional]
+        name: String = "anonymous".to_string(),
+        #[cow]
+        #[variant_optional]
+        tags: Vec<u32>,
+    }]
+    enum E {
+        A,
+        #[common_fields(skip(name, tags))]
+        B,
+    }
+
+    let a = E::A {
+        user: "rhysd".to_string(),
+        name: "foo".to_string(),
+        tags: vec![1, 2],
+    };
+    let user: Cow<'_, str> = a.user();
+    assert_eq!(user, "rhysd");
+    assert!(matches!(a.name(), Cow::Borrowed("foo")));
+    assert_eq!(a.tags(), Some(Cow::Borrowed(&[1, 2][..])));
+
+    let b = E::B {
+        user: "rhysd".to_string(),
+    };
+    let name: Cow<'_, str> = b.name();
+    assert!(matches!(name, Cow::Owned(ref s) if s == "anonymous"));
+    assert_eq!(b.tags(), None);
+}
+
+#[test]
+fn generic_parameter_defaults() {
+    #[common_fields(common_struct, kind, builder, view, transition, into, partial_eq, hash, default = "B", {
+        x: T,
+        #[variant_optional]
+        y: u32,
+    })]
+    #[derive(Debug)]
+    enum E<T: Default + PartialEq + core::hash::Hash = String> {
+        A {
+            a: bool,
+        },
+        #[common_fields(skip(y))]
+        B,
+    }
+
+    let e: E = E::A {
+        a: true,
+        x: "foo".to_string(),
+        y: 1,
+    };
+    assert_eq!(e.x(), "foo");
+    assert_eq!(e.kind(), EKind::A);
+    assert!(e.as_a().is_some());
+    assert_eq!(E::<String>::default().x(), "");
+
+    let e: E = E::a_builder()
+        .a(false)
+        .x("bar".to_string())
+        .y(2)
+        .build()
+        .unwrap();
+    let e = e.with_b();
+    assert_eq!(e.y(), None);
+    let c: ECommon = e.into_common();
+    assert_eq!(c.x, "bar");
+}
+
+#[test]
+fn ord_key_sort() {
+    #[common_fields {
+        name: &'static str,
+        #[ord_key]
+        stars: u32,
+    }]
+    #[derive(Debug)]
+    enum E {
+        A { a: bool },
+        B,
+    }
+
+    // Equality should be consistent with the ordering by the key
+    impl PartialEq for E {
+        fn eq(&self, other: &Self) -> bool {
+            self.stars() == other.stars()
+        }
+    }
+    impl Eq for E {}
+
+    let mut v = vec![
+        E::B {
+            name: "foo",
+            stars: 3,
+        },
+        E::A {
+            a: true,
+            name: "bar",
+            stars: 1,
+        },
+        E::B {
+            name: "piyo",
+            stars: 2,
+        },
+    ];
+    v.push(E::A {
+        a: false,
+        name: "hoge",
+        stars: 0,
+    });
+    v.sort();
+    let names = v.iter().map(|e| *e.name()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["hoge", "bar", "piyo", "foo"]);
+    assert!(v[0] < v[1]);
+    assert_eq!(v.iter().max().unwrap().name(), &"foo");
+}
+
+#[test]
+fn associated_type_common_field() {
+    trait Backend {
+        type Id;
+        type Meta: Clone;
+    }
+    struct Local;
+    impl Backend for Local {
+        type Id = u64;
+        type Meta = String;
+    }
+
+    #[common_fields(common_struct, builder, into, accessors = "get,set,mut", where T: Backend, {
+        id: <T as Backend>::Id,
+        #[variant_optional]
+        meta: Vec<T::Meta>,
+    })]
+    enum E<T> {
+        A {
+            b: bool,
+        },
+        #[common_fields(skip(meta))]
+        B,
+    }
+
+    let mut e: E<Local> = E::A {
+        b: true,
+        id: 42,
+        meta: vec!["foo".to_string()],
+    };
+    let id: &u64 = e.id();
+    assert_eq!(*id, 42);
+    e.set_id(43);
+    e.meta_mut().unwrap().push("bar".to_string());
+    assert_eq!(e.meta(), Some(&vec!["foo".to_string(), "bar".to_string()]));
+    assert_eq!(e.into_common().id, 43);
+
+    let e: E<Local> = E::b_builder().id(1).build().unwrap();
+    assert_eq!(e.meta(), None);
+    assert_eq!(e.into_id(), 1);
+}
+
+#[test]
+fn variant_name_method() {
+    #[common_fields { user: String }]
+    enum RemoteRepo {
+        GitHub { language: String },
+        GitLab,
+        Local,
+    }
+
+    let repo = RemoteRepo::GitHub {
+        language: "rust".to_string(),
+        user: "rhysd".to_string(),
+    };
+    assert_eq!(repo.variant_name(), "GitHub");
+    let repo = RemoteRepo::GitLab {
+        user: "rhysd".to_string(),
+    };
+    assert_eq!(repo.variant_name(), "GitLab");
+    let repo = RemoteRepo::Local {
+        user: "rhysd".to_string(),
+    };
+    assert_eq!(repo.variant_name(), "Local");
+}
+
+#[test]
+fn single_variant_enum() {
+    #[common_fields(into, kind, common_refs, accessors = "get,set,mut", {
+        x: i32,
+        #[variant_optional]
+        y: u32,
+    })]
+    #[deny(unreachable_patterns)]
+    enum E {
+        A { a: bool },
+    }
+
+    let mut e = E::A {
+        a: true,
+        x: 42,
+        y: 1,
+    };
+    assert_eq!(e.x(), &42);
+    e.set_x(43);
+    assert_eq!(e.y(), Some(&1));
+    assert_eq!(e.common_refs(), (&43, Some(&1)));
+    assert!(e.is_a());
+    assert_eq!(e.kind(), EKind::A);
+    assert_eq!(e.variant_name(), "A");
+    assert_eq!(e.into_x(), 43);
+}
+
+#[test]
+fn empty_enum() {
+    #[common_fields(into, common_refs, kind, partial_eq, hash, {
+        #[as_ref]
+        #[pin]
+        x: i32,
+        #[variant_optional]
+        y: u32,
+    })]
+    #[allow(dead_code)]
+    enum E {}
+
+    fn f(e: &E) -> &i32 {
+        e.x()
+    }
+    let _ = f;
+    assert_eq!(E::common_field_names(), &["x", "y"]);
+}
+
+#[test]
+fn map_common_fields() {
+    #[common_fields(map, {
+        user: String,
+        #[variant_optional]
+        stars: u32,
+        #[accessor(name = "repo_name")]
+        name: String,
+    })]
+    #[derive(Debug, PartialEq)]
+    enum E {
+        A {
+            user_id: u32,
+        },
+        #[common_fields(skip(stars))]
+        B,
+    }
+
+    let e = E::A {
+        user_id: 1,
+        user: "rhysd".to_string(),
+        stars: 2,
+        name: "foo".to_string(),
+    };
+    let e = e.map_user(|u| u.to_uppercase()).map_stars(|s| s * 10);
+    assert_eq!(
+        e,
+        E::A {
+            user_id: 1,
+            user: "RHYSD".to_string(),
+            stars: 20,
+            name: "foo".to_string(),
+        }
+    );
+
+    let e = E::B {
+        user: "rhysd".to_string(),
+        name: "foo".to_string(),
+    };
+    let e = e
+        .map_stars(|_| panic!("B does not have stars"))
+        .map_repo_name(|n| n + "!");
+    assert_eq!(e.repo_name(), "foo!");
+
+    #[common_fields(map, tuple, { x: i32 })]
+    #[derive(Debug, PartialEq)]
+    enum T {
+        A(bool),
+        B,
+    }
+    assert_eq!(T::A(true, 1).map_x(|x| x + 1), T::A(true, 2));
+    assert_eq!(T::B(1).map_x(|x| -x), T::B(-1));
+}
+
+#[test]
+fn recursive_enum_with_derives() {
+    #[common_fields(partial_eq, { parent: Option<Box<Self>> })]
+    #[derive(Clone, Debug)]
+    enum Node {
+        Leaf { value: i32 },
+        Branch { children: Vec<Self> },
+    }
+
+    let root = Node::Branch {
+        children: vec![],
+        parent: None,
+    };
+    let leaf = Node::Leaf {
+        value: 1,
+        parent: Some(Box::new(root.clone())),
+    };
+    let cloned = leaf.clone();
+    assert_eq!(cloned, leaf);
+    assert!(cloned.parent().as_ref().unwrap().is_branch());
+    assert_eq!(
+        format!("{:?}", cloned),
+        "Leaf { value: 1, parent: Some(Branch { children: [], parent: None }) }"
+    );
+}
+
+#[test]
+fn accessors_option() {
+    #[common_fields(accessors = "get", { x: i32 })]
+    enum Get {
+        A,
+    }
+    assert_eq!(Get::A { x: 1 }.x(), &1);
+
+    #[common_fields(accessors = "get,set", { x: i32 })]
+    enum GetSet {
+        A,
+    }
+    let mut e = GetSet::A { x: 1 };
+    e.set_x(2);
+    assert_eq!(e.x(), &2);
+
+    #[common_fields(accessors = "mut,set", { x: i32 })]
+    enum MutSet {
+        A,
+    }
+    let mut e = MutSet::A { x: 1 };
+    e.set_x(2);
+    *e.x_mut() += 1;
+    let MutSet::A { x } = e;
+    assert_eq!(x, 3);
+}
+
+#[test]
+fn non_exhaustive_enum_in_other_crate() {
+    use diff_enum_upstream_test::{HasUser, Repo, RepoKind};
+
+    // Implementing the generated trait in downstream crate for its own type
+    struct Account {
+        user: String,
+    }
+    impl HasUser for Account {
+        fn user(&self) -> &String {
+            &self.user
+        }
+        fn user_mut(&mut self) -> &mut String {
+            &mut self.user
+        }
+        fn set_user(&mut self, value: String) {
+            self.user = value;
+        }
+        fn stars(&self) -> Option<&u32> {
+            None
+        }
+        fn stars_mut(&mut self) -> Option<&mut u32> {
+            None
+        }
+    }
+
+    fn user_of(v: &impl HasUser) -> &str {
+        v.user()
+    }
+
+    let mut repo = Repo::GitHub {
+        language: "rust".to_string(),
+        user: "rhysd".to_string(),
+        stars: 10,
+    };
+    repo.set_user("foo".to_string());
+    assert_eq!(user_of(&repo), "foo");
+    assert_eq!(repo.stars(), Some(&10));
+    assert_eq!(repo.kind(), RepoKind::GitHub);
+
+    let mut account = Account {
+        user: "bar".to_string(),
+    };
+    account.user_mut().push('!');
+    assert_eq!(user_of(&account), "bar!");
+
+    // Only matches in downstream crates need wildcard arms. Generated code is in the upstream crate
+    let repo = Repo::local("piyo");
+    let language = match repo {
+        Repo::GitHub { ref language, .. } => Some(language.as_str()),
+        Repo::Local { .. } => None,
+        _ => unreachable!(),
+    };
+    assert_eq!(language, None);
+    assert_eq!(repo.user(), "piyo");
+}
+
+#[test]
+fn common_field_visibility() {
+    mod repo {
+        use diff_enum::common_fields;
+
+        #[common_fields(common_struct, {
+            pub user: String,
+            pub(crate) name: String,
+        })]
+        pub enum Repo {
+            GitHub { stars: u32 },
+            Local,
+        }
+    }
+
+    // Fields of variants are as visible as the enum so they can be matched in other modules
+    let r = repo::Repo::GitHub {
+        stars: 1,
+        user: "rhysd".to_string(),
+        name: "foo".to_string(),
+    };
+    match r {
+        repo::Repo::GitHub { ref user, .. } => assert_eq!(user, "rhysd"),
+        repo::Repo::Local { .. } => unreachable!(),
+    }
+
+    let c = r.into_common();
+    assert_eq!(c.user, "rhysd");
+    assert_eq!(c.name, "foo");
+}
+
+#[test]
+fn prefix_common_field_names() {
+    #[common_fields(prefix = "common_", common_struct, accessors = "get,set,mut", {
+        id: u32,
+        #[variant_optional]
+        r#type: String,
+    })]
+    enum E {
+        A {
+            id: String,
+        },
+        #[common_fields(skip(r#type))]
+        B,
+    }
+
+    let mut e = E::A {
+        id: "foo".to_string(),
+        common_id: 42,
+        common_type: "bar".to_string(),
+    };
+    assert_eq!(e.common_id(), &42);
+    e.set_common_id(43);
+    assert_eq!(e.common_type().map(String::as_str), Some("bar"));
+    assert_eq!(E::common_field_names(), &["common_id", "common_type"]);
+    if let E::A { ref id, .. } = e {
+        assert_eq!(id, "foo");
+    }
+    assert_eq!(e.into_common().common_id, 43);
+
+    let e = E::B { common_id: 1 };
+    assert_eq!(e.common_type(), None);
+}
+
+#[test]
+fn variant_constructors() {
+    #[common_fields(constructor, {
+        user: String,
+        #[variant_optional]
+        stars: u32,
+    })]
+    #[derive(Debug, PartialEq)]
+    enum RemoteRepo {
+        GitHub {
+            language: String,
+            pull_requests: u32,
+        },
+        #[common_fields(skip(stars))]
+        Local,
+    }
+
+    let repo = RemoteRepo::new_git_hub("rust".to_string(), 1, "rhysd".to_string(), 42);
+    assert_eq!(
+        repo,
+        RemoteRepo::GitHub {
+            language: "rust".to_string(),
+            pull_requests: 1,
+            user: "rhysd".to_string(),
+            stars: 42,
+        }
+    );
+    let repo = RemoteRepo::new_local("rhysd".to_string());
+    assert_eq!(repo.user(), "rhysd");
+    assert!(repo.is_local());
+
+    #[common_fields(constructor, tuple, prepend, { x: i32 })]
+    #[derive(Debug, PartialEq)]
+    enum T {
+        A(bool),
+        B,
+    }
+    assert_eq!(T::new_a(1, true), T::A(1, true));
+    assert_eq!(T::new_b(2), T::B(2));
+}
+
+#[test]
+fn common_method_returning_impl_trait() {
+    use diff_enum::common_methods;
+
+    #[common_fields {
+        tags: Vec<String>,
+    }]
+    #[common_methods {
+        pub fn long_tags<'a>(&'a self, len: usize) -> impl Iterator<Item = &'a String> + 'a {
+            self.tags().iter().filter(move |t| t.len() >= len)
+        }
+
+        pub fn tag_lens(&self) -> impl Iterator<Item = usize> + '_ {
+            self.tags().iter().map(String::len)
+        }
+    }]
+    enum E {
+        A { a: bool },
+        B,
+    }
+
+    let e = E::A {
+        a: true,
+        tags: vec!["rust".to_string(), "go".to_string(), "c++".to_string()],
+    };
+    let tags = e.long_tags(3).collect::<Vec<_>>();
+    assert_eq!(tags, vec!["rust", "c++"]);
+    assert_eq!(e.tag_lens().sum::<usize>(), 9);
+}
+
+#[test]
+fn lower_case_variant_names() {
+    #[common_fields(case = "lower", view, builder, transition, constructor, {
+        user: String,
+    })]
+    enum RemoteRepo {
+        GitHub { stars: u32 },
+        GitLab,
+    }
+
+    let repo = RemoteRepo::github_builder()
+        .stars(1)
+        .user("rhysd".to_string())
+        .build()
+        .unwrap();
+    assert!(repo.is_github());
+    assert_eq!(repo.as_github().map(|v| *v.stars), Some(1));
+    let repo = repo.with_gitlab();
+    assert!(repo.is_gitlab());
+    let repo = RemoteRepo::new_gitlab("foo".to_string());
+    assert_eq!(repo.user(), "foo");
+}
+
+#[test]
+fn enum_in_nested_module() {
+    mod outer {
+        // Generated items must not leak into this module
+        #[allow(dead_code)]
+        pub struct RepoCommon;
+        #[allow(dead_code)]
+        pub struct RepoKind;
+        #[allow(dead_code)]
+        pub struct RepoGitHubRef;
+
+        pub mod inner {
+            use diff_enum::common_fields;
+
+            #[common_fields(common_struct, kind, view, builder, trait = "HasUser", {
+                pub user: String,
+            })]
+            #[derive(Debug)]
+            pub enum Repo {
+                GitHub { stars: u32 },
+                Local,
+            }
+        }
+    }
+
+    use outer::inner::{HasUser, Repo, RepoCommon, RepoGitHubRef, RepoKind};
+
+    let repo = Repo::git_hub_builder()
+        .stars(3)
+        .user("rhysd".to_string())
+        .build()
+        .unwrap();
+    assert_eq!(repo.kind(), RepoKind::GitHub);
+    assert_eq!(HasUser::user(&repo), "rhysd");
+    let RepoGitHubRef { stars } = repo.as_git_hub().unwrap();
+    assert_eq!(*stars, 3);
+    let common: RepoCommon = repo.into_common();
+    assert_eq!(common.user, "rhysd");
+}
+
+#[test]
+fn display_common_field() {
+    #[common_fields {
+        #[display]
+        name: String,
+        stars: u32,
+    }]
+    enum RemoteRepo {
+        GitHub { language: String },
+        Local,
+    }
+
+    let repo = RemoteRepo::GitHub {
+        language: "rust".to_string(),
+        name: "diff-enum".to_string(),
+        stars: 3,
+    };
+    assert_eq!(format!("{}", repo), "diff-enum");
+    let repo = RemoteRepo::Local {
+        name: "local".to_string(),
+        stars: 0,
+    };
+    assert_eq!(repo.to_string(), "local");
+
+    #[common_fields { #[display] #[variant_optional] url: &'static str, id: u32 }]
+    enum Source {
+        Remote,
+        #[common_fields(skip(url))]
+        Local,
+    }
+
+    let src = Source::Remote {
+        url: "https://github.com",
+        id: 1,
+    };
+    assert_eq!(format!("{}", src), "https://github.com");
+    assert_eq!(format!("{}", Source::Local { id: 2 }), "");
+}
+
+#[test]
+fn grouped_common_fields() {
+    #[common_fields(accessors = "get,set,mut", {
+        name: String,
+        #[group(remote)]
+        api_url: String,
+        #[group(remote, mirror)]
+        last_sync: u64,
+        #[group(mirror)]
+        upstream: &'static str,
+    })]
+    #[derive(Debug, PartialEq)]
+    enum Repo {
+        #[groups(remote)]
+        GitHub {
+            stars: u32,
+        },
+        #[groups(remote, mirror)]
+        GitLabMirror,
+        #[groups(mirror)]
+        LocalMirror,
+        Local,
+    }
+
+    let github = Repo::GitHub {
+        stars: 3,
+        name: "diff-enum".to_string(),
+        api_url: "https://api.github.com".to_string(),
+        last_sync: 10,
+    };
+    assert_eq!(github.name(), "diff-enum");
+    assert_eq!(
+        github.api_url().map(String::as_str),
+        Some("https://api.github.com")
+    );
+    assert_eq!(github.last_sync(), Some(&10));
+    assert_eq!(github.upstream(), None);
+
+    let gitlab = Repo::GitLabMirror {
+        name: "foo".to_string(),
+        api_url: "https://gitlab.com/api/v4".to_string(),
+        last_sync: 20,
+        upstream: "https://github.com/rhysd/foo",
+    };
+    assert!(gitlab.api_url().is_some());
+    assert_eq!(gitlab.last_sync(), Some(&20));
+    assert_eq!(gitlab.upstream(), Some(&"https://github.com/rhysd/foo"));
+
+    let mut mirror = Repo::LocalMirror {
+        name: "bar".to_string(),
+        last_sync: 30,
+        upstream: "/path/to/bar",
+    };
+    assert_eq!(mirror.api_url(), None);
+    assert_eq!(mirror.upstream(), Some(&"/path/to/bar"));
+    if let Some(t) = mirror.last_sync_mut() {
+        *t += 1;
+    }
+    assert_eq!(mirror.last_sync(), Some(&31));
+
+    let mut local = Repo::Local {
+        name: "baz".to_string(),
+    };
+    assert_eq!(local.api_url(), None);
+    assert_eq!(local.last_sync(), None);
+    assert_eq!(local.upstream(), None);
+    assert!(local.last_sync_mut().is_none());
+    local.set_name("qux".to_string());
+    assert_eq!(
+        local,
+        Repo::Local {
+            name: "qux".to_string()
+        }
+    );
+}
+
+#[test]
+fn replace_common_field() {
+    #[common_fields(accessors = "get,set,mut", {
+        name: String,
+        stars: u32,
+    })]
+    enum RemoteRepo {
+        GitHub { language: String },
+        Local,
+    }
+
+    let mut repo = RemoteRepo::GitHub {
+        language: "rust".to_string(),
+        name: "diff-enum".to_string(),
+        stars: 3,
+    };
+    assert_eq!(repo.replace_stars(10), 3);
+    assert_eq!(repo.stars(), &10);
+    let old = repo.replace_name("foo".to_string());
+    assert_eq!(old, "diff-enum");
+    assert_eq!(repo.name(), "foo");
+
+    let mut repo = RemoteRepo::Local {
+        name: "local".to_string(),
+        stars: 0,
+    };
+    assert_eq!(repo.replace_stars(1), 0);
+    assert_eq!(repo.stars(), &1);
+}
+
+#[test]
+fn complex_common_field_types() {
+    fn double(x: u32) -> u32 {
+        x * 2
+    }
+    fn first(s: &str) -> &str {
+        &s[..1]
+    }
+
+    #[common_fields(accessors = "get,set,mut", {
+        callback: fn(u32) -> u32,
+        hrtb: for<'a> fn(&'a str) -> &'a str,
+        pair: (u8, &'static str),
+        array: [i32; 3],
+        slice: &'static [u8],
+    })]
+    enum E {
+        A { a: bool },
+        B,
+    }
+
+    let mut e = E::A {
+        a: true,
+        callback: double,
+        hrtb: first,
+        pair: (1, "one"),
+        array: [1, 2, 3],
+        slice: b"foo",
+    };
+    let callback: &fn(u32) -> u32 = e.callback();
+    assert_eq!(callback(21), 42);
+    assert_eq!((e.hrtb())("diff"), "d");
+    let pair: &(u8, &str) = e.pair();
+    assert_eq!(pair, &(1, "one"));
+    let array: &[i32; 3] = e.array();
+    assert_eq!(array, &[1, 2, 3]);
+    let slice: &&[u8] = e.slice();
+    assert_eq!(*slice, b"foo");
+
+    e.array_mut()[0] = 10;
+    e.set_callback(|x| x + 1);
+    e.set_pair((2, "two"));
+    assert_eq!(e.array(), &[10, 2, 3]);
+    assert_eq!((e.callback())(1), 2);
+    assert_eq!(e.pair().1, "two");
+
+    let e = E::B {
+        callback: double,
+        hrtb: first,
+        pair: (0, ""),
+        array: [0; 3],
+        slice: &[],
+    };
+    assert!(e.slice().is_empty());
+}
+
+#[test]
+fn parenthesized_common_fields() {
+    #[common_fields(user: String, name: String)]
+    #[derive(Debug, PartialEq)]
+    enum Paren {
+        A { a: bool },
+        B,
+    }
+
+    #[common_fields(prepend, kind, #[variant_optional] stars: u32, user: String)]
+    enum ParenWithOptions {
+        A,
+        #[common_fields(skip(stars))]
+        B,
+    }
+
+    #[common_fields {
+        user: String,
+        name: String,
+    }]
+    #[derive(Debug, PartialEq)]
+    enum Braces {
+        A { a: bool },
+        B,
+    }
+
+    let p = Paren::A {
+        a: true,
+        user: "rhysd".to_string(),
+        name: "diff-enum".to_string(),
+    };
+    let b = Braces::A {
+        a: true,
+        user: "rhysd".to_string(),
+        name: "diff-enum".to_string(),
+    };
+    assert_eq!(p.user(), b.user());
+    assert_eq!(p.name(), b.name());
+
+    let e = ParenWithOptions::B {
+        user: "foo".to_string(),
+    };
+    assert_eq!(e.kind(), ParenWithOptionsKind::B);
+    assert_eq!(e.stars(), None);
+    assert_eq!(e.user(), "foo");
+}
+
+#[test]
+#[allow(deprecated)]
+fn deprecated_common_field() {
+    #[common_fields(trait = "HasOld", partial_eq, accessors = "get,set,mut", {
+        #[deprecated = "use new instead"]
+        old: u32,
+        new: u32,
+    })]
+    enum E {
+        A,
+    }
+
+    #[common_fields(into, accessors = "get,set,mut", {
+        #[deprecated]
+        old: u32,
+    })]
+    enum F {
+        A,
+    }
+
+    let mut e = E::A { old: 1, new: 2 };
+    e.set_old(3);
+    assert_eq!(e.old(), &3);
+    assert_eq!(F::A { old: 4 }.into_old(), 4);
+}
+
+#[test]
+fn lifetime_and_type_generics() {
+    #[common_fields(
+        common_struct,
+        kind,
+        view,
+        transition,
+        constructor,
+        map,
+        partial_eq,
+        hash,
+        accessors = "get,set,mut",
+        {
+            label: &'a str,
+            data: T,
+        }
+    )]
+    #[derive(Debug)]
+    enum E<'a, T: Clone + 'a> {
+        A { r: &'a T },
+        B { s: &'a [T] },
+        C,
+    }
+
+    let v = vec![1, 2, 3];
+    let mut e: E<'_, i32> = E::A {
+        r: &v[0],
+        label: "first",
+        data: 10,
+    };
+    assert_eq!(e.label(), &"first");
+    assert_eq!(e.data(), &10);
+    *e.data_mut() += 1;
+    e.set_label("one");
+    assert_eq!(e.kind(), EKind::A);
+    assert_eq!(e.as_a().map(|v| **v.r), Some(1));
+
+    let e = e.map_data(|d| d * 2);
+    assert_eq!(e.data(), &22);
+    let label: &str = e.common().label;
+    assert_eq!(label, "one");
+
+    let e = e.with_b(&v);
+    assert_eq!(e.as_b().map(|v| v.s.len()), Some(3));
+    assert_eq!(e, E::new_b(&v[..], "one", 22));
+
+    let common: ECommon<'_, i32> = e.into_common();
+    assert_eq!(common.label, "one");
+    let e = E::c_with_common(common);
+    assert!(e.is_c());
+    assert_eq!(e.data(), &22);
 }
@@ -0,0 +1,59 @@
+// Checks that code generated by the macros can be compiled in no_std crate. Building this crate is
+// enough for the check.
+
+#![no_std]
+
+use diff_enum::{common_fields, common_match, common_methods};
+
+#[common_fields(
+    into,
+    copy,
+    common_struct,
+    kind,
+    partial_eq,
+    hash,
+    builder,
+    view,
+    default = "A",
+    {
+        #[as_ref]
+        x: i32,
+        #[variant_optional]
+        y: u32,
+    }
+)]
+#[common_methods {
+    pub fn sum(&self) -> i64 {
+        self.x() as i64 + self.y().unwrap_or(0) as i64
+    }
+}]
+#[derive(Debug, Clone, Copy)]
+pub enum E {
+    A {
+        a: bool,
+    },
+    #[common_fields(skip(y))]
+    B,
+}
+
+#[common_fields(tuple, trait = "HasName", {
+    name: &'static str,
+})]
+pub enum T {
+    A(u8),
+    B,
+}
+
+pub fn check() -> bool {
+    let e = E::a_builder().a(true).x(1).y(2).build().unwrap_or_default();
+    let c: ECommon = e.into();
+    let n = T::A(0, "foo");
+    e.is_a()
+        && e.kind() == EKind::A
+        && e.as_a().is_some()
+        && e != E::B { x: 1 }
+        && c.x == 1
+        && e.sum() == 3
+        && common_match!(e, { x, y } => *x == 1 && y == Some(&2))
+        && *n.name() == "foo"
+}
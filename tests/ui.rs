@@ -0,0 +1,10 @@
+#![cfg(test)]
+
+// Checks diagnostics of compilation errors caused by the macros. Run the tests with TRYBUILD=overwrite
+// environment variable to update the expected outputs in tests/ui/*.stderr.
+
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
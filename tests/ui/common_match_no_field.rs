@@ -0,0 +1,11 @@
+use diff_enum::{common_fields, common_match};
+
+#[common_fields { x: i32 }]
+enum E {
+    A,
+}
+
+fn main() {
+    let e = E::A { x: 1 };
+    let _ = common_match!(e, {} => 0);
+}
@@ -0,0 +1,14 @@
+use diff_enum::common_fields;
+
+// Only getters are defined without `accessors` option
+#[common_fields { x: i32 }]
+enum E {
+    A,
+}
+
+fn main() {
+    let mut e = E::A { x: 1 };
+    let _ = e.x();
+    e.set_x(2);
+    *e.x_mut() = 3;
+}
@@ -0,0 +1,24 @@
+#![deny(deprecated)]
+
+use diff_enum::common_fields;
+
+#[common_fields(accessors = "get,set,mut", {
+    #[deprecated = "use y instead"]
+    x: i32,
+    y: i32,
+})]
+enum E {
+    A,
+}
+
+#[allow(deprecated)]
+fn new() -> E {
+    E::A { x: 1, y: 2 }
+}
+
+fn main() {
+    let mut e = new();
+    e.y();
+    e.x();
+    e.set_x(3);
+}
@@ -0,0 +1,14 @@
+use diff_enum::common_fields;
+
+// The derive is expanded before common fields are injected. Its helper attribute tells the mistake
+#[derive(Debug, Default)]
+#[common_fields {
+    x: i32,
+}]
+enum E {
+    #[default]
+    A,
+    B { b: bool },
+}
+
+fn main() {}
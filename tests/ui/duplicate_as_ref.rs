@@ -0,0 +1,13 @@
+use diff_enum::common_fields;
+
+#[common_fields {
+    #[as_ref(str)]
+    user: String,
+    #[as_ref(str)]
+    name: String,
+}]
+enum E {
+    A,
+}
+
+fn main() {}
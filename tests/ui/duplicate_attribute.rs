@@ -0,0 +1,10 @@
+use diff_enum::common_fields;
+
+#[common_fields { x: i32 }]
+#[common_fields { y: u32 }]
+#[common_fields { x: i32 }]
+enum E {
+    A,
+}
+
+fn main() {}
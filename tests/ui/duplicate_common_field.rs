@@ -0,0 +1,12 @@
+use diff_enum::common_fields;
+
+#[common_fields {
+    x: i32,
+    y: u32,
+    x: String,
+}]
+enum E {
+    A,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use diff_enum::common_fields;
+
+#[common_fields { x: i32 }]
+enum E {
+    A { x: i32 },
+    B,
+}
+
+fn main() {}
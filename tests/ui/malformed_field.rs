@@ -0,0 +1,8 @@
+use diff_enum::common_fields;
+
+#[common_fields { x: i32; y: i32 }]
+enum E {
+    A,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use diff_enum::common_fields;
+
+#[common_fields(kind, {
+    kind: String,
+})]
+enum E {
+    A,
+}
+
+fn main() {}
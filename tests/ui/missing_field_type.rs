@@ -0,0 +1,11 @@
+use diff_enum::common_fields;
+
+#[common_fields {
+    x: i32,
+    y,
+}]
+enum E {
+    A,
+}
+
+fn main() {}
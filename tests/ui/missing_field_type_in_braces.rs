@@ -0,0 +1,12 @@
+use diff_enum::common_fields;
+
+#[common_fields(prepend, {
+    x: i32,
+    /// Doc comment
+    y,
+})]
+enum E {
+    A,
+}
+
+fn main() {}
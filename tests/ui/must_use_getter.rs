@@ -0,0 +1,14 @@
+#![deny(unused_must_use)]
+
+use diff_enum::common_fields;
+
+#[common_fields(must_use, { x: i32 })]
+enum E {
+    A,
+}
+
+fn main() {
+    let e = E::A { x: 1 };
+    e.x();
+    e.is_a();
+}
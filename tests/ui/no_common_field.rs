@@ -0,0 +1,8 @@
+use diff_enum::common_fields;
+
+#[common_fields(prepend)]
+enum E {
+    A,
+}
+
+fn main() {}
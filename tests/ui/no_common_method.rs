@@ -0,0 +1,9 @@
+use diff_enum::{common_fields, common_methods};
+
+#[common_fields { x: i32 }]
+#[common_methods()]
+enum E {
+    A,
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use diff_enum::common_fields;
+
+#[common_fields { x: i32 }]
+struct S {
+    y: i32,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use diff_enum::common_fields;
+
+#[common_fields(allow_override, { x: i32 })]
+enum E {
+    A { x: u32 },
+    B,
+}
+
+fn main() {}
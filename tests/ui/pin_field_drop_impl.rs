@@ -0,0 +1,17 @@
+use diff_enum::common_fields;
+use std::marker::PhantomPinned;
+
+// `Drop::drop()` takes `&mut self` so it could move the pinned field out
+#[common_fields {
+    #[pin]
+    pinned: PhantomPinned,
+}]
+enum E {
+    A,
+}
+
+impl Drop for E {
+    fn drop(&mut self) {}
+}
+
+fn main() {}
@@ -0,0 +1,16 @@
+use diff_enum::common_fields;
+use std::marker::PhantomPinned;
+
+#[common_fields {
+    #[pin]
+    pinned: PhantomPinned,
+}]
+enum E {
+    A,
+}
+
+fn assert_unpin<T: Unpin>() {}
+
+fn main() {
+    assert_unpin::<E>();
+}
@@ -0,0 +1,15 @@
+use diff_enum::common_fields;
+use std::marker::PhantomPinned;
+
+// Implementing `Unpin` would allow moving the pinned field out of `Pin<&mut E>`
+#[common_fields {
+    #[pin]
+    pinned: PhantomPinned,
+}]
+enum E {
+    A,
+}
+
+impl Unpin for E {}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use diff_enum::common_fields;
+
+#[common_fields(case = "lower", {
+    x: i32,
+})]
+enum E {
+    GitHub { y: i32 },
+    Github { z: i32 },
+}
+
+fn main() {}
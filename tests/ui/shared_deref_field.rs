@@ -0,0 +1,11 @@
+use diff_enum::common_fields;
+
+#[common_fields(shared, {
+    #[deref]
+    user: String,
+})]
+enum E {
+    A,
+}
+
+fn main() {}
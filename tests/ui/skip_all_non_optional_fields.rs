@@ -0,0 +1,14 @@
+use diff_enum::common_fields;
+
+#[common_fields {
+    #[variant_optional]
+    x: i32,
+    y: i32,
+}]
+enum E {
+    A,
+    #[common_fields(skip)]
+    Invalid,
+}
+
+fn main() {}
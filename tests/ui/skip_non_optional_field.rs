@@ -0,0 +1,10 @@
+use diff_enum::common_fields;
+
+#[common_fields { x: i32 }]
+enum E {
+    #[common_fields(skip(x))]
+    A,
+    B,
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use diff_enum::common_fields;
+
+#[common_fields {
+    #[variant_optional]
+    x: i32,
+}]
+enum E {
+    #[common_fields(skip(y))]
+    A,
+    B,
+}
+
+fn main() {}
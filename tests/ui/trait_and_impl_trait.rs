@@ -0,0 +1,8 @@
+use diff_enum::common_fields;
+
+#[common_fields(trait = "HasX", impl_trait = "HasX", { x: i32 })]
+enum E {
+    A,
+}
+
+fn main() {}
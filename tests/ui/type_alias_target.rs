@@ -0,0 +1,10 @@
+use diff_enum::common_fields;
+
+enum E {
+    A,
+}
+
+#[common_fields { x: i32 }]
+type Alias = E;
+
+fn main() {}
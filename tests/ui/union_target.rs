@@ -0,0 +1,9 @@
+use diff_enum::common_fields;
+
+#[common_fields { x: i32 }]
+union U {
+    a: u32,
+    b: f32,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use diff_enum::common_fields;
+
+#[common_fields(default = "C", { x: i32 })]
+enum E {
+    A,
+    B,
+}
+
+fn main() {}
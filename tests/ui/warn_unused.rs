@@ -0,0 +1,15 @@
+#![deny(dead_code)]
+
+use diff_enum::common_fields;
+
+#[common_fields(warn_unused, accessors = "get,set,mut", { x: i32 })]
+pub(crate) enum E {
+    A,
+    B,
+}
+
+fn main() {
+    let e = E::A { x: 42 };
+    assert!(e.is_a());
+    let _ = e.x();
+}
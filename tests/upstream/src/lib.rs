@@ -0,0 +1,27 @@
+// Defines an enum with the macros for tests in another crate. `#[non_exhaustive]` only affects crates
+// other than the one defining the enum, so generated code must work when it is used from downstream.
+
+use diff_enum::common_fields;
+
+#[common_fields(trait = "HasUser", kind, accessors = "get,set,mut", {
+    user: String,
+    #[variant_optional]
+    stars: u32,
+})]
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum Repo {
+    GitHub {
+        language: String,
+    },
+    #[common_fields(skip(stars))]
+    Local,
+}
+
+impl Repo {
+    pub fn local(user: &str) -> Self {
+        Repo::Local {
+            user: user.to_string(),
+        }
+    }
+}